@@ -0,0 +1,780 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::bail;
+use rayon::prelude::*;
+use robin_cli_core::chapter_number::volume_label;
+use robin_cli_core::comicinfo::{ComicInfo, COMIC_INFO_FILE};
+use robin_cli_core::credits::SKIPPED_DIR;
+use robin_cli_core::manifest::{Manifest, ManifestEntry, MANIFEST_FILE};
+use sevenz_rust::{Password, SevenZArchiveEntry, SevenZReader, SevenZWriter};
+use zip::{write::FileOptions, CompressionMethod, DateTime, ZipArchive, ZipWriter};
+
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "gif", "webp", "avif"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    /// Zip with another extension, for comic readers
+    Cbz,
+    Tar,
+    TarGz,
+    TarZst,
+    SevenZ,
+}
+
+impl ArchiveFormat {
+    /// Format of an archive file from its extension.
+    pub fn from_name(name: &str) -> Option<ArchiveFormat> {
+        let name = name.to_ascii_lowercase();
+
+        [
+            ArchiveFormat::Zip,
+            ArchiveFormat::Cbz,
+            ArchiveFormat::Tar,
+            ArchiveFormat::TarGz,
+            ArchiveFormat::TarZst,
+            ArchiveFormat::SevenZ,
+        ]
+        .into_iter()
+        .find(|f| name.ends_with(&format!(".{}", f.extension())))
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => "zip",
+            ArchiveFormat::Cbz => "cbz",
+            ArchiveFormat::Tar => "tar",
+            ArchiveFormat::TarGz => "tar.gz",
+            ArchiveFormat::TarZst => "tar.zst",
+            ArchiveFormat::SevenZ => "7z",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ArchiveOptions {
+    /// Method for images. They are already compressed, so Stored is usually the best
+    pub image_method: CompressionMethod,
+    /// Method for everything else (txt, json, xml)
+    pub text_method: CompressionMethod,
+    /// Compression level, also used for tar.gz and tar.zst
+    pub level: Option<i32>,
+    /// Threads compressing entries (zip) or the stream (tar.zst)
+    pub threads: usize,
+}
+
+impl Default for ArchiveOptions {
+    fn default() -> Self {
+        ArchiveOptions {
+            image_method: CompressionMethod::Stored,
+            text_method: CompressionMethod::Deflated,
+            level: None,
+            threads: 1,
+        }
+    }
+}
+
+impl ArchiveOptions {
+    fn entry_method(&self, name: &str) -> CompressionMethod {
+        let ext = name
+            .rsplit_once('.')
+            .map(|(_, ext)| ext.to_ascii_lowercase())
+            .unwrap_or_default();
+
+        match IMAGE_EXTENSIONS.contains(&ext.as_str()) {
+            true => self.image_method,
+            false => self.text_method,
+        }
+    }
+
+    fn entry_options(&self, name: &str, size: u64) -> FileOptions {
+        let method = self.entry_method(name);
+
+        // Fixed timestamp and permissions, the same files always give the same archive
+        FileOptions::default()
+            .compression_method(method)
+            .compression_level(match method {
+                CompressionMethod::Stored => None,
+                _ => self.level,
+            })
+            .last_modified_time(DateTime::default())
+            .unix_permissions(0o644)
+            .large_file(size >= u32::MAX as u64)
+    }
+}
+
+/// Files in `root` as (name in the archive, path), sorted by name.
+/// Moved aside credit pages are left out.
+pub fn collect_entries(root: &Path) -> anyhow::Result<Vec<(String, PathBuf)>> {
+    let mut entries = Vec::new();
+
+    for ent in walkdir::WalkDir::new(root)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter(|e| !e.path().starts_with(root.join(SKIPPED_DIR)))
+    {
+        // Archives always use `/`, whatever the OS
+        let name = ent
+            .path()
+            .strip_prefix(root)?
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        entries.push((name, ent.into_path()));
+    }
+
+    entries.sort();
+
+    Ok(entries)
+}
+
+fn add_entry<W: io::Write + io::Seek>(
+    zipper: &mut ZipWriter<W>,
+    name: &str,
+    path: &Path,
+    opts: &ArchiveOptions,
+) -> anyhow::Result<()> {
+    let mut f = File::open(path)?;
+    let size = f.metadata()?.len();
+
+    zipper.start_file(name, opts.entry_options(name, size))?;
+    io::copy(&mut f, zipper)?;
+
+    Ok(())
+}
+
+fn write_zip(
+    entries: &[(String, PathBuf)],
+    destination: &Path,
+    opts: &ArchiveOptions,
+    mut on_entry: impl FnMut(),
+) -> anyhow::Result<()> {
+    let mut zipper = ZipWriter::new(BufWriter::new(File::create(destination)?));
+
+    if opts.threads > 1 {
+        // Entries are compressed into their own small archives in parallel,
+        // then their compressed data is copied over in order
+        let work_dir = destination.with_extension("entries");
+        fs::create_dir_all(&work_dir)?;
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(opts.threads)
+            .build()?;
+
+        let compressed = pool.install(|| {
+            entries
+                .par_iter()
+                .enumerate()
+                .map(|(i, (name, path))| {
+                    // Nothing to compress, copied straight from the file later
+                    if opts.entry_method(name) == CompressionMethod::Stored {
+                        return Ok(None);
+                    }
+
+                    let options = opts.entry_options(name, fs::metadata(path)?.len());
+
+                    let part = work_dir.join(format!("{}.zip", i));
+                    let mut single = ZipWriter::new(BufWriter::new(File::create(&part)?));
+                    single.start_file(name, options)?;
+                    io::copy(&mut File::open(path)?, &mut single)?;
+                    single.finish()?;
+
+                    Ok(Some(part))
+                })
+                .collect::<anyhow::Result<Vec<Option<PathBuf>>>>()
+        })?;
+
+        for ((name, path), part) in entries.iter().zip(compressed) {
+            match part {
+                Some(part) => {
+                    let mut single = ZipArchive::new(BufReader::new(File::open(&part)?))?;
+                    zipper.raw_copy_file(single.by_index_raw(0)?)?;
+                    fs::remove_file(&part)?;
+                }
+                None => add_entry(&mut zipper, name, path, opts)?,
+            }
+            on_entry();
+        }
+
+        fs::remove_dir_all(&work_dir)?;
+    } else {
+        for (name, path) in entries {
+            add_entry(&mut zipper, name, path, opts)?;
+            on_entry();
+        }
+    }
+
+    zipper.finish()?;
+
+    Ok(())
+}
+
+fn write_tar<W: Write>(
+    entries: &[(String, PathBuf)],
+    writer: W,
+    mut on_entry: impl FnMut(),
+) -> anyhow::Result<W> {
+    let mut builder = tar::Builder::new(writer);
+
+    for (name, path) in entries {
+        let mut f = File::open(path)?;
+
+        // No owner or timestamp, same as zip
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Regular);
+        header.set_size(f.metadata()?.len());
+        header.set_mode(0o644);
+        header.set_mtime(0);
+
+        builder.append_data(&mut header, name, &mut f)?;
+        on_entry();
+    }
+
+    Ok(builder.into_inner()?)
+}
+
+fn write_7z(
+    entries: &[(String, PathBuf)],
+    destination: &Path,
+    mut on_entry: impl FnMut(),
+) -> anyhow::Result<()> {
+    let mut writer = SevenZWriter::create(destination)?;
+
+    for (name, path) in entries {
+        // No timestamps or attributes, same as zip
+        let mut entry = SevenZArchiveEntry::new();
+        entry.name = name.clone();
+        entry.has_stream = true;
+
+        writer.push_archive_entry(entry, Some(BufReader::new(File::open(path)?)))?;
+        on_entry();
+    }
+
+    writer.finish()?;
+
+    Ok(())
+}
+
+/// Writes every file in `root` into an archive at `destination`, streaming them from disk.
+/// Entries are sorted and have no timestamps, so the same files always give the same archive.
+/// `on_entry` is called after each entry.
+pub fn write_archive(
+    root: &Path,
+    destination: &Path,
+    format: ArchiveFormat,
+    opts: &ArchiveOptions,
+    on_entry: impl FnMut(),
+) -> anyhow::Result<()> {
+    write_entries(&collect_entries(root)?, destination, format, opts, on_entry)
+}
+
+fn write_entries(
+    entries: &[(String, PathBuf)],
+    destination: &Path,
+    format: ArchiveFormat,
+    opts: &ArchiveOptions,
+    on_entry: impl FnMut(),
+) -> anyhow::Result<()> {
+    match format {
+        ArchiveFormat::Zip | ArchiveFormat::Cbz => write_zip(entries, destination, opts, on_entry)?,
+        ArchiveFormat::Tar => {
+            let f = BufWriter::new(File::create(destination)?);
+            write_tar(entries, f, on_entry)?.flush()?;
+        }
+        ArchiveFormat::TarGz => {
+            let level = match opts.level {
+                Some(level) => flate2::Compression::new(level.clamp(0, 9) as u32),
+                None => flate2::Compression::default(),
+            };
+            let f = BufWriter::new(File::create(destination)?);
+            let encoder = flate2::write::GzEncoder::new(f, level);
+            write_tar(entries, encoder, on_entry)?.finish()?.flush()?;
+        }
+        ArchiveFormat::TarZst => {
+            let f = BufWriter::new(File::create(destination)?);
+            // Level 0 is the zstd default
+            let mut encoder = zstd::Encoder::new(f, opts.level.unwrap_or(0))?;
+            if opts.threads > 1 {
+                encoder.multithread(opts.threads as u32)?;
+            }
+            write_tar(entries, encoder, on_entry)?.finish()?.flush()?;
+        }
+        ArchiveFormat::SevenZ => write_7z(entries, destination, on_entry)?,
+    }
+
+    Ok(())
+}
+
+/// Like `write_archive`, but writes to a `.part` file first so only a finished archive gets the real name.
+pub fn archive_to(
+    root: &Path,
+    destination: &Path,
+    format: ArchiveFormat,
+    opts: &ArchiveOptions,
+    on_entry: impl FnMut(),
+) -> anyhow::Result<()> {
+    let partial = part_path(destination);
+
+    write_archive(root, &partial, format, opts, on_entry)?;
+    fs::rename(&partial, destination)?;
+
+    Ok(())
+}
+
+fn part_path(destination: &Path) -> PathBuf {
+    let mut partial = destination.as_os_str().to_owned();
+    partial.push(".part");
+    PathBuf::from(partial)
+}
+
+/// Archives every chapter folder of `root` on its own into `destination`.
+/// Other files (cover, details...) are copied next to them.
+pub fn archive_chapters(
+    root: &Path,
+    destination: &Path,
+    format: ArchiveFormat,
+    opts: &ArchiveOptions,
+    mut on_entry: impl FnMut(),
+) -> anyhow::Result<()> {
+    fs::create_dir_all(destination)?;
+
+    let mut items = fs::read_dir(root)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .collect::<Vec<PathBuf>>();
+    items.sort();
+
+    for path in items {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+        // Moved aside credit pages and staging leftovers
+        if name == SKIPPED_DIR || name.starts_with('.') {
+            continue;
+        }
+
+        if path.is_dir() {
+            let archive_name = format!("{}.{}", name, format.extension());
+            archive_to(
+                &path,
+                &destination.join(archive_name),
+                format,
+                opts,
+                &mut on_entry,
+            )?;
+        } else {
+            fs::copy(&path, destination.join(name.as_ref()))?;
+            on_entry();
+        }
+    }
+
+    Ok(())
+}
+
+/// One archive per volume named `<name> Vol. N`. `groups` are the top level chapter folders
+/// of each volume (see `Manifest::volume_groups`), top level files go into every archive
+/// with a `ComicInfo.xml` for the volume.
+pub fn archive_volumes(
+    root: &Path,
+    destination: &Path,
+    name: &str,
+    groups: &[(Option<f64>, Vec<String>)],
+    format: ArchiveFormat,
+    opts: &ArchiveOptions,
+    mut on_entry: impl FnMut(),
+) -> anyhow::Result<Vec<PathBuf>> {
+    fs::create_dir_all(destination)?;
+
+    let entries = collect_entries(root)?;
+    let manifest = match root.join(MANIFEST_FILE).exists() {
+        true => Some(Manifest::load(root)?),
+        false => None,
+    };
+    let mut archives = Vec::new();
+
+    for (volume, dirs) in groups {
+        let label = volume_label(*volume);
+        let archive_name = format!("{} {}", name, label);
+
+        let in_volume = |entry: &str| match entry.split_once('/') {
+            Some((top, _)) => dirs.iter().any(|d| d == top),
+            None => entry != COMIC_INFO_FILE && entry != MANIFEST_FILE,
+        };
+
+        let mut volume_entries = entries
+            .iter()
+            .filter(|(entry, _)| in_volume(entry))
+            .cloned()
+            .collect::<Vec<(String, PathBuf)>>();
+
+        let comic_info = ComicInfo {
+            series: name.to_string(),
+            title: label,
+            volume: *volume,
+            ..Default::default()
+        };
+        let comic_info_xml = comic_info.to_xml();
+        let comic_info_path = destination.join(format!(".{}.{}", archive_name, COMIC_INFO_FILE));
+        fs::write(&comic_info_path, &comic_info_xml)?;
+        volume_entries.push((COMIC_INFO_FILE.to_string(), comic_info_path.clone()));
+
+        // Only the files of this volume, so `robin verify` can check the archive on its own
+        let manifest_path = destination.join(format!(".{}.{}", archive_name, MANIFEST_FILE));
+        if let Some(manifest) = &manifest {
+            let mut volume_manifest = Manifest {
+                files: Vec::new(),
+                ..manifest.clone()
+            };
+            volume_manifest.insert(
+                manifest
+                    .files
+                    .iter()
+                    .filter(|e| in_volume(&e.path))
+                    .cloned()
+                    .chain([ManifestEntry::from_bytes(
+                        COMIC_INFO_FILE.to_string(),
+                        comic_info_xml.as_bytes(),
+                        manifest.source.clone(),
+                    )]),
+            );
+            fs::write(
+                &manifest_path,
+                serde_json::to_string_pretty(&volume_manifest)?,
+            )?;
+            volume_entries.push((MANIFEST_FILE.to_string(), manifest_path.clone()));
+        }
+        volume_entries.sort();
+
+        let archive = destination.join(format!("{}.{}", archive_name, format.extension()));
+        let partial = part_path(&archive);
+        let result = write_entries(&volume_entries, &partial, format, opts, &mut on_entry);
+        fs::remove_file(&comic_info_path)?;
+        if manifest.is_some() {
+            fs::remove_file(&manifest_path)?;
+        }
+        result?;
+        fs::rename(&partial, &archive)?;
+
+        archives.push(archive);
+    }
+
+    Ok(archives)
+}
+
+fn read_tar(
+    reader: impl Read,
+    each: &mut impl FnMut(&str, &mut dyn Read) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let mut archive = tar::Archive::new(reader);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.header().entry_type().is_file() {
+            let name = entry.path()?.to_string_lossy().into_owned();
+            each(&name, &mut entry)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Calls `each` with the name and content of every file of an archive written by robin.
+pub fn read_archive(
+    path: &Path,
+    mut each: impl FnMut(&str, &mut dyn Read) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let Some(format) = ArchiveFormat::from_name(&path.to_string_lossy()) else {
+        bail!("{} is not an archive", path.display());
+    };
+
+    match format {
+        ArchiveFormat::Zip | ArchiveFormat::Cbz => {
+            let mut zip = ZipArchive::new(BufReader::new(File::open(path)?))?;
+            for i in 0..zip.len() {
+                let mut file = zip.by_index(i)?;
+                if file.is_file() {
+                    let name = file.name().to_string();
+                    each(&name, &mut file)?;
+                }
+            }
+        }
+        ArchiveFormat::Tar => read_tar(BufReader::new(File::open(path)?), &mut each)?,
+        ArchiveFormat::TarGz => {
+            let decoder = flate2::read::GzDecoder::new(BufReader::new(File::open(path)?));
+            read_tar(decoder, &mut each)?
+        }
+        ArchiveFormat::TarZst => read_tar(zstd::Decoder::new(File::open(path)?)?, &mut each)?,
+        ArchiveFormat::SevenZ => {
+            // The 7z callback can only fail with its own error type
+            let mut error = None;
+            SevenZReader::open(path, Password::empty())?.for_each_entries(|entry, reader| {
+                if entry.is_directory() {
+                    return Ok(true);
+                }
+                match each(entry.name(), reader) {
+                    Ok(()) => Ok(true),
+                    Err(e) => {
+                        error = Some(e);
+                        Ok(false)
+                    }
+                }
+            })?;
+            if let Some(e) = error {
+                return Err(e);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Moves the credit pages set aside in the work folder `root` to `_skipped/<name>` in
+/// `destination`, before the work folder is removed. Returns where they went, if anywhere.
+pub fn keep_skipped(
+    root: &Path,
+    destination: &Path,
+    name: &str,
+) -> anyhow::Result<Option<PathBuf>> {
+    let skipped = root.join(SKIPPED_DIR);
+    if !skipped.is_dir() {
+        return Ok(None);
+    }
+
+    let target = destination.join(SKIPPED_DIR).join(name);
+
+    // File by file, so pages of earlier runs stay and --temp-dir can be on another disk
+    for entry in walkdir::WalkDir::new(&skipped)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+    {
+        let to = target.join(entry.path().strip_prefix(&skipped)?);
+        fs::create_dir_all(to.parent().unwrap())?;
+
+        if fs::rename(entry.path(), &to).is_err() {
+            fs::copy(entry.path(), &to)?;
+            fs::remove_file(entry.path())?;
+        }
+    }
+
+    Ok(Some(target))
+}
+
+#[cfg(test)]
+mod archive_tests {
+    use super::*;
+
+    fn sample_serie(root: &Path) {
+        for ch in ["Chapter 2", "Chapter 1"] {
+            fs::create_dir_all(root.join(ch)).unwrap();
+            fs::write(root.join(ch).join("0001.jpg"), [0xFF, 0xD8, 0xFF, 1, 2, 3]).unwrap();
+            fs::write(root.join(ch).join("0000.jpg"), [0xFF, 0xD8, 0xFF, 4, 5, 6]).unwrap();
+        }
+        fs::write(root.join("details.json"), "{\"title\": \"uwu\"}".repeat(50)).unwrap();
+        fs::create_dir_all(root.join(SKIPPED_DIR)).unwrap();
+        fs::write(root.join(SKIPPED_DIR).join("credits.jpg"), [0xFF]).unwrap();
+    }
+
+    #[test]
+    fn test_entries_are_sorted() {
+        let dir = tempfile::tempdir().unwrap();
+        sample_serie(dir.path());
+
+        let names = collect_entries(dir.path())
+            .unwrap()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<String>>();
+
+        assert_eq!(
+            names,
+            [
+                "Chapter 1/0000.jpg",
+                "Chapter 1/0001.jpg",
+                "Chapter 2/0000.jpg",
+                "Chapter 2/0001.jpg",
+                "details.json"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_archives_are_reproducible() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("serie");
+        sample_serie(&root);
+
+        for threads in [1, 4] {
+            let opts = ArchiveOptions {
+                threads,
+                ..Default::default()
+            };
+            let first = dir.path().join(format!("first-{}.zip", threads));
+            let second = dir.path().join(format!("second-{}.zip", threads));
+
+            write_archive(&root, &first, ArchiveFormat::Zip, &opts, || {}).unwrap();
+            write_archive(&root, &second, ArchiveFormat::Zip, &opts, || {}).unwrap();
+
+            assert_eq!(fs::read(&first).unwrap(), fs::read(&second).unwrap());
+
+            let mut archive = ZipArchive::new(File::open(&first).unwrap()).unwrap();
+            assert_eq!(archive.len(), 5);
+
+            let mut details = archive.by_name("details.json").unwrap();
+            assert_eq!(details.compression(), CompressionMethod::Deflated);
+            let mut contents = String::new();
+            io::Read::read_to_string(&mut details, &mut contents).unwrap();
+            assert_eq!(contents, "{\"title\": \"uwu\"}".repeat(50));
+            drop(details);
+
+            assert_eq!(
+                archive.by_name("Chapter 1/0000.jpg").unwrap().compression(),
+                CompressionMethod::Stored
+            );
+        }
+    }
+
+    #[test]
+    fn test_other_formats_are_reproducible() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("serie");
+        sample_serie(&root);
+
+        for format in [
+            ArchiveFormat::Tar,
+            ArchiveFormat::TarGz,
+            ArchiveFormat::TarZst,
+            ArchiveFormat::SevenZ,
+        ] {
+            let first = dir.path().join(format!("first.{}", format.extension()));
+            let second = dir.path().join(format!("second.{}", format.extension()));
+
+            let opts = ArchiveOptions::default();
+            write_archive(&root, &first, format, &opts, || {}).unwrap();
+            write_archive(&root, &second, format, &opts, || {}).unwrap();
+
+            assert_eq!(fs::read(&first).unwrap(), fs::read(&second).unwrap());
+        }
+
+        let mut tar = tar::Archive::new(File::open(dir.path().join("first.tar")).unwrap());
+        let names = tar
+            .entries()
+            .unwrap()
+            .map(|e| e.unwrap().path().unwrap().to_string_lossy().to_string())
+            .collect::<Vec<String>>();
+        assert_eq!(names.len(), 5);
+        assert_eq!(names[0], "Chapter 1/0000.jpg");
+    }
+
+    #[test]
+    fn test_archive_per_chapter() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("serie");
+        let destination = dir.path().join("out");
+        sample_serie(&root);
+
+        let mut count = 0;
+        archive_chapters(
+            &root,
+            &destination,
+            ArchiveFormat::Cbz,
+            &ArchiveOptions::default(),
+            || count += 1,
+        )
+        .unwrap();
+
+        assert_eq!(count, 5);
+        assert!(destination.join("details.json").exists());
+        assert!(!destination.join(SKIPPED_DIR).exists());
+
+        let cbz = File::open(destination.join("Chapter 1.cbz")).unwrap();
+        let archive = ZipArchive::new(cbz).unwrap();
+        let mut names = archive.file_names().collect::<Vec<&str>>();
+        names.sort();
+        assert_eq!(names, ["0000.jpg", "0001.jpg"]);
+    }
+
+    #[test]
+    fn test_archive_per_volume() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("serie");
+        let destination = dir.path().join("out");
+        sample_serie(&root);
+
+        let groups = [
+            (Some(1.0), vec!["Chapter 1".to_string()]),
+            (None, vec!["Chapter 2".to_string()]),
+        ];
+        let archives = archive_volumes(
+            &root,
+            &destination,
+            "uwu",
+            &groups,
+            ArchiveFormat::Cbz,
+            &ArchiveOptions::default(),
+            || {},
+        )
+        .unwrap();
+
+        assert_eq!(
+            archives,
+            [
+                destination.join("uwu Vol. 1.cbz"),
+                destination.join("uwu No Volume.cbz")
+            ]
+        );
+
+        let cbz = File::open(&archives[0]).unwrap();
+        let mut archive = ZipArchive::new(cbz).unwrap();
+        let mut names = archive.file_names().collect::<Vec<&str>>();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                "Chapter 1/0000.jpg",
+                "Chapter 1/0001.jpg",
+                "ComicInfo.xml",
+                "details.json"
+            ]
+        );
+
+        let mut xml = String::new();
+        io::Read::read_to_string(&mut archive.by_name("ComicInfo.xml").unwrap(), &mut xml).unwrap();
+        assert!(xml.contains("<Volume>1</Volume>"));
+
+        // Temporary ComicInfo files are cleaned up
+        assert_eq!(fs::read_dir(&destination).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_skipped_pages_outlive_the_work_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join(".uwu.robin");
+        let destination = dir.path().join("out");
+        sample_serie(&root);
+        fs::create_dir_all(&destination).unwrap();
+
+        archive_to(
+            &root,
+            &destination.join("uwu.cbz"),
+            ArchiveFormat::Cbz,
+            &ArchiveOptions::default(),
+            || {},
+        )
+        .unwrap();
+        let kept = keep_skipped(&root, &destination, "uwu").unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(kept, Some(destination.join(SKIPPED_DIR).join("uwu")));
+        assert!(destination
+            .join(SKIPPED_DIR)
+            .join("uwu")
+            .join("credits.jpg")
+            .exists());
+        assert_eq!(keep_skipped(&root, &destination, "uwu").unwrap(), None);
+    }
+}
@@ -1,53 +1,387 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use chrono::NaiveDate;
 use clap::{Parser, Subcommand, ValueEnum};
+use robin_cli_core::dates::{local_today, parse_date};
+use robin_cli_core::naming::{NameTemplate, Templates};
+use robin_cli_core::cookies::JAR_FILE;
+use robin_cli_core::net::{check_rate, CookieStoreMutex, NetOptions, RateLimit};
+use robin_cli_core::processing::TargetFormat;
+use robin_cli_core::selection::{ChapterOrder, ChapterSelection};
+use robin_cli_core::stitch::StitchMode;
+use zip::CompressionMethod;
+
+use crate::archive::ArchiveFormat;
+use crate::config::{default_data_dir, Config};
 
-#[derive(Parser)]
+#[derive(Parser, Clone)]
 #[command(version, about, long_about = None)]
 pub struct App {
     #[command(subcommand)]
     pub command: Commands,
 
     /// Where should downloaded serie stay huh?
-    #[arg(short, long)]
+    #[arg(short, long, default_value = ".", env = "ROBIN_OUTPUT_FOLDER")]
     pub output_folder: String,
 
     /// Number of chapters that will be downloaded at the same time
-    #[arg(short, long, default_value_t = 1)]
+    #[arg(short, long, default_value_t = 1, env = "ROBIN_CONCURRENT_CHAPTERS")]
     pub concurrent_chapters: usize,
 
     /// Proxy URL. Example: http://uwu.com:8080
-    #[arg(short, long, default_value_t = String::new())]
+    #[arg(short, long, default_value_t = String::new(), env = "ROBIN_PROXY")]
     pub proxy: String,
+
+    /// Name of the serie folder, archive or ebook.
+    /// Placeholders: {title} {source} {lang} {date}
+    #[arg(long, default_value = "{title}", env = "ROBIN_SERIES_TEMPLATE")]
+    pub series_template: NameTemplate,
+
+    /// Name of chapter folders (manga) or chapter files (novel).
+    /// Placeholders: {title} {source} {lang} {volume} {chapter} {chapter_title} {date}.
+    /// Numbers can be zero padded: {chapter:04.1} -> 0012.5
+    #[arg(long, default_value = "{chapter_title}", env = "ROBIN_CHAPTER_TEMPLATE")]
+    pub chapter_template: NameTemplate,
+
+    /// Name of page files (without extension). Same placeholders as chapters plus {page}
+    #[arg(long, default_value = "{page:04}", env = "ROBIN_PAGE_TEMPLATE")]
+    pub page_template: NameTemplate,
+
+    /// Folder for unfinished downloads (default: inside the output folder)
+    #[arg(long, env = "ROBIN_TEMP_DIR")]
+    pub temp_dir: Option<PathBuf>,
+
+    /// User agent sent with every request
+    #[arg(long, env = "ROBIN_USER_AGENT")]
+    pub user_agent: Option<String>,
+
+    /// Page requests per second, shared by the chapters downloaded at the same time
+    #[arg(long, env = "ROBIN_RATE_LIMIT", value_parser = parse_rate_arg)]
+    pub rate_limit: Option<f64>,
+
+    /// Config file (default: $XDG_CONFIG_HOME/robin/config.toml)
+    #[arg(long, env = "ROBIN_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Named set of options: kindle, archive, or a [profiles.<name>] table of the config file
+    #[arg(long, env = "ROBIN_PROFILE")]
+    pub profile: Option<String>,
+
+    /// Where robin keeps its cookie jar (default: $XDG_DATA_HOME/robin)
+    #[arg(long, env = "ROBIN_DATA_DIR")]
+    pub data_dir: Option<PathBuf>,
+
+    /// Loaded from the config file, see `apply_config`
+    #[arg(skip)]
+    pub settings: Config,
+
+    /// Global options given on the command line or in the environment
+    #[arg(skip)]
+    pub explicit: Vec<String>,
+
+    /// Cookie jar of the data folder, shared by every client of the run
+    #[arg(skip)]
+    pub cookies: Option<Arc<CookieStoreMutex>>,
+
+    /// Limit shared with the entries of `robin batch` on the same site, see `page_limit`
+    #[arg(skip)]
+    pub shared_limit: Option<RateLimit>,
+}
+
+impl App {
+    pub fn net(&self) -> NetOptions {
+        NetOptions {
+            proxy: self.proxy.clone(),
+            user_agent: self.user_agent.clone(),
+            cookies: self.cookies.clone(),
+        }
+    }
+
+    pub fn page_limit(&self) -> Option<RateLimit> {
+        if self.shared_limit.is_some() {
+            return self.shared_limit.clone();
+        }
+
+        self.rate_limit
+            .filter(|r| *r > 0.0)
+            .map(RateLimit::per_second)
+    }
+
+    pub fn jar_path(&self) -> Option<PathBuf> {
+        self.data_dir
+            .clone()
+            .or_else(default_data_dir)
+            .map(|dir| dir.join(JAR_FILE))
+    }
+
+    pub fn templates(&self) -> Templates {
+        Templates {
+            series: self.series_template.clone(),
+            chapter: self.chapter_template.clone(),
+            page: self.page_template.clone(),
+        }
+    }
 }
 
 #[derive(Clone, Subcommand)]
 pub enum Commands {
     Manga {
-        #[arg(short, long, default_value_t = false)]
-        compress: bool,
+        /// Pack the download into archives instead of leaving folders
+        #[arg(long)]
+        archive: Option<ArchiveArg>,
 
-        /// Chapter filter. Example 1: 10:100 Example 2: 20.5:100.3  (Note: both numbers included)
-        #[arg(long, default_value_t = String::new())]
-        filter: String,
+        /// One archive for the whole serie or one per chapter
+        #[arg(long, default_value = "series")]
+        archive_per: ArchivePer,
 
-        /// URL of the source content
+        /// One archive per volume instead (`Title Vol. 2.cbz`), chapters without a volume go together
+        #[arg(long, requires = "archive", conflicts_with = "archive_per")]
+        group_by: Option<GroupBy>,
+
+        /// Chapters to download, comma separated: 10, 10:20, 100:, :20, !13, first:10, latest:5, whole (skip .5 extras)
+        #[arg(long, default_value = "all")]
+        filter: ChapterSelection,
+
+        /// Only chapters released on or after this date: 2024-03-01, "2 weeks ago"
+        #[arg(long, value_parser = parse_date_arg)]
+        since: Option<NaiveDate>,
+
+        /// Only chapters released on or before this date
+        #[arg(long, value_parser = parse_date_arg)]
+        until: Option<NaiveDate>,
+
+        /// Order chapters are downloaded in
+        #[arg(long, default_value = "source")]
+        sort: SortArg,
+
+        /// URL of the source content, a serie or a single chapter
         url: String,
+
+        /// Download a chapter URL on its own, without looking up its serie (raw pages only)
+        #[arg(long, default_value_t = false, conflicts_with = "archive")]
+        no_series: bool,
+
+        /// Convert WebP/AVIF pages to this format
+        #[arg(long)]
+        convert_to: Option<PageFormat>,
+
+        /// Downscale pages wider than this many pixels
+        #[arg(long)]
+        max_width: Option<u32>,
+
+        /// Downscale pages taller than this many pixels
+        #[arg(long)]
+        max_height: Option<u32>,
+
+        /// Convert pages to grayscale (for e-ink readers)
+        #[arg(long, default_value_t = false)]
+        grayscale: bool,
+
+        /// Re-encode JPEG pages with this quality (1-100)
+        #[arg(long, value_parser = clap::value_parser!(u8).range(1..=100))]
+        jpeg_quality: Option<u8>,
+
+        /// Stitch webtoon strips of each chapter together, then split them again or keep one tall image
+        #[arg(long)]
+        stitch: Option<StitchArg>,
+
+        /// Target page height in pixels for `--stitch split` (default: based on page width)
+        #[arg(long)]
+        page_height: Option<u32>,
+
+        /// Drop pages that repeat across chapters (scanlator credits, recruitment and ad pages)
+        #[arg(long, default_value_t = false)]
+        drop_credits: bool,
+
+        /// Minimum number of chapters a page has to appear in to be dropped by `--drop-credits`
+        #[arg(long, default_value_t = 3)]
+        credit_min_chapters: usize,
+
+        /// Folder of images that are always dropped when a page looks like one of them
+        #[arg(long)]
+        credit_blocklist: Option<PathBuf>,
+
+        /// Move dropped pages to a `_skipped` folder instead of deleting them (they are not compressed,
+        /// with --archive they go to `_skipped/<serie>` in the output folder)
+        #[arg(long, default_value_t = false)]
+        move_credits: bool,
+
+        /// How many times a broken page (error page, truncated image...) is downloaded again
+        #[arg(long, default_value_t = 3)]
+        page_retries: usize,
+
+        /// Compression of images in the archive. They are already compressed, so storing is the fastest
+        #[arg(long, default_value = "store")]
+        image_compression: CompressionArg,
+
+        /// Compression of everything else in the archive (txt, json, xml)
+        #[arg(long, default_value = "deflate")]
+        text_compression: CompressionArg,
+
+        /// Compression level. Deflate: 0-9, Zstd: 1-22 (default: method default)
+        #[arg(long)]
+        compression_level: Option<i32>,
+
+        /// Number of threads compressing archive entries
+        #[arg(long, default_value_t = 1)]
+        archive_threads: usize,
     },
 
     Novel {
-        // URL of the source content
+        // URL of the source content, a novel or a single chapter
         url: String,
 
-        /// Chapter filter. Example 1: 10:100 Example 2: 20.5:100.3  (Note: both numbers included)
-        #[arg(long, default_value_t = String::new())]
-        filter: String,
+        /// Download a chapter URL on its own as a text file, without looking up its novel
+        #[arg(long, default_value_t = false)]
+        no_series: bool,
+
+        /// Chapters to download, comma separated: 10, 10:20, 100:, :20, !13, first:10, latest:5, whole (skip .5 extras)
+        #[arg(long, default_value = "all")]
+        filter: ChapterSelection,
 
         /// Format of the downloaded novel
         /// Default: txt
         #[arg(long, default_value_t = NovelFormat::default())]
         format: NovelFormat,
+
+        /// One epub per volume (`Title Vol. 2.epub`), chapters without a volume go together
+        #[arg(long)]
+        group_by: Option<GroupBy>,
+    },
+
+    /// Show the metadata and chapter count of a serie without downloading anything
+    Info {
+        /// URL of the source content
+        url: String,
+
+        /// Print JSON instead of text
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+
+    /// List the chapters of a serie without downloading anything
+    Chapters {
+        /// URL of the source content
+        url: String,
+
+        /// Chapters to list, same as for downloads: 10, 10:20, 100:, :20, !13, first:10, latest:5, whole
+        #[arg(long, default_value = "all")]
+        filter: ChapterSelection,
+
+        /// Print JSON instead of a table
+        #[arg(long, default_value_t = false, conflicts_with = "csv")]
+        json: bool,
+
+        /// Print CSV instead of a table
+        #[arg(long, default_value_t = false)]
+        csv: bool,
+
+        /// Report missing, duplicated, out of order and unnumbered chapters instead
+        #[arg(long, default_value_t = false)]
+        check: bool,
+    },
+
+    /// Check a downloaded serie folder or archive against its checksum manifest
+    Verify {
+        /// Serie folder (the one with robin-manifest.json) or archive
+        path: PathBuf,
+
+        /// Download chapters with missing or corrupted files again (folders only)
+        #[arg(long, default_value_t = false)]
+        repair: bool,
+    },
+
+    /// Download a manga or novel, whichever the source hosts: cbz for manga and epub for novels by default
+    Get {
+        /// URL of the source content
+        url: String,
+
+        /// Archive format for manga (`folder` for none), txt or epub for novels
+        #[arg(long)]
+        format: Option<String>,
+
+        /// Any other `manga`/`novel` option, after `--`: robin get <url> -- --filter latest:5
+        #[arg(last = true)]
+        options: Vec<String>,
+    },
+
+    /// Download every serie listed in a file: one URL per line, or TOML/JSON entries with options
+    Batch {
+        /// URL list, .toml or .json file
+        file: PathBuf,
+
+        /// Number of series downloaded at the same time
+        #[arg(short, long, default_value_t = 1)]
+        jobs: usize,
+    },
+
+    /// Manage the cookie jar sent to every site (Cloudflare clearance, logins...)
+    Cookies {
+        #[command(subcommand)]
+        action: CookiesAction,
+    },
+}
+
+#[derive(Subcommand, Clone)]
+pub enum CookiesAction {
+    /// Add cookies from a Netscape cookies.txt or a JSON export of a browser extension
+    Import {
+        file: PathBuf,
+
+        /// Only import the cookies of this domain (and its subdomains)
+        #[arg(long)]
+        domain: Option<String>,
+    },
+
+    /// Show the cookies of the jar, without their values
+    List {
+        /// Only cookies of this domain
+        #[arg(long)]
+        domain: Option<String>,
+    },
+
+    /// Write the jar as a Netscape cookies.txt
+    Export {
+        file: PathBuf,
+
+        /// Only cookies of this domain
+        #[arg(long)]
+        domain: Option<String>,
+    },
+
+    /// Remove cookies of a domain, or all of them
+    Clear {
+        /// Only cookies of this domain
+        #[arg(long)]
+        domain: Option<String>,
     },
 }
 
+impl Commands {
+    /// The serie or chapter URL of the command, if it has one.
+    pub fn url(&self) -> Option<&str> {
+        match self {
+            Commands::Manga { url, .. }
+            | Commands::Novel { url, .. }
+            | Commands::Info { url, .. }
+            | Commands::Chapters { url, .. }
+            | Commands::Get { url, .. } => Some(url),
+            Commands::Verify { .. } | Commands::Batch { .. } | Commands::Cookies { .. } => None,
+        }
+    }
+}
+
+fn parse_date_arg(s: &str) -> Result<NaiveDate, String> {
+    parse_date(s, "en", local_today())
+        .ok_or_else(|| format!("\"{}\" is not a date, try 2024-03-01 or \"2 weeks ago\"", s))
+}
+
+fn parse_rate_arg(s: &str) -> Result<f64, String> {
+    let rate = s.parse::<f64>().map_err(|e| e.to_string())?;
+    check_rate(rate).map_err(|e| e.to_string())
+}
+
 #[derive(ValueEnum, Clone, Default, Debug)]
 pub enum NovelFormat {
     /// Chapters as text files
@@ -65,4 +399,110 @@ impl std::fmt::Display for NovelFormat {
             NovelFormat::Epub => write!(f, "epub"),
         }
     }
-}
\ No newline at end of file
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum PageFormat {
+    Jpeg,
+    Png,
+}
+
+impl From<PageFormat> for TargetFormat {
+    fn from(value: PageFormat) -> Self {
+        match value {
+            PageFormat::Jpeg => TargetFormat::Jpeg,
+            PageFormat::Png => TargetFormat::Png,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum StitchArg {
+    /// Re-split at whitespace gutters into screen sized pages
+    Split,
+    /// Output one tall image per chapter
+    Tall,
+}
+
+impl StitchArg {
+    pub fn to_mode(self, page_height: Option<u32>) -> StitchMode {
+        match self {
+            StitchArg::Split => StitchMode::Split { page_height },
+            StitchArg::Tall => StitchMode::Tall,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum CompressionArg {
+    Store,
+    Deflate,
+    Zstd,
+}
+
+impl From<CompressionArg> for CompressionMethod {
+    fn from(value: CompressionArg) -> Self {
+        match value {
+            CompressionArg::Store => CompressionMethod::Stored,
+            CompressionArg::Deflate => CompressionMethod::Deflated,
+            CompressionArg::Zstd => CompressionMethod::Zstd,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ArchiveArg {
+    Zip,
+    Cbz,
+    Tar,
+    #[value(name = "tar.gz")]
+    TarGz,
+    #[value(name = "tar.zst")]
+    TarZst,
+    #[value(name = "7z")]
+    SevenZ,
+}
+
+impl From<ArchiveArg> for ArchiveFormat {
+    fn from(value: ArchiveArg) -> Self {
+        match value {
+            ArchiveArg::Zip => ArchiveFormat::Zip,
+            ArchiveArg::Cbz => ArchiveFormat::Cbz,
+            ArchiveArg::Tar => ArchiveFormat::Tar,
+            ArchiveArg::TarGz => ArchiveFormat::TarGz,
+            ArchiveArg::TarZst => ArchiveFormat::TarZst,
+            ArchiveArg::SevenZ => ArchiveFormat::SevenZ,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum GroupBy {
+    Volume,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ArchivePer {
+    Series,
+    Chapter,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum SortArg {
+    /// As listed by the source
+    Source,
+    /// By chapter number
+    Number,
+    /// By release date
+    Date,
+}
+
+impl From<SortArg> for ChapterOrder {
+    fn from(value: SortArg) -> Self {
+        match value {
+            SortArg::Source => ChapterOrder::Source,
+            SortArg::Number => ChapterOrder::Number,
+            SortArg::Date => ChapterOrder::Date,
+        }
+    }
+}
@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::bail;
+use futures::StreamExt;
+use robin_cli_core::sources::ContentKind;
+use serde::Deserialize;
+
+use crate::args::App;
+use crate::config::host;
+use crate::get::{command_args, sub_app};
+use crate::run;
+
+/// One serie of a batch file, options left out use the command defaults.
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BatchEntry {
+    url: String,
+    /// Found from the URL when missing
+    #[serde(rename = "type")]
+    kind: Option<ContentKind>,
+    filter: Option<String>,
+    /// Archive format for manga (`folder` for none), txt or epub for novels
+    format: Option<String>,
+    /// Subfolder of the output folder
+    output: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct BatchFile {
+    series: Vec<BatchEntry>,
+}
+
+fn parse_entries(path: &Path, text: &str) -> anyhow::Result<Vec<BatchEntry>> {
+    let ext = path.extension().unwrap_or_default().to_string_lossy();
+
+    let entries = match ext.to_lowercase().as_str() {
+        "toml" => toml::from_str::<BatchFile>(text)?.series,
+        "json" => match serde_json::from_str::<Vec<BatchEntry>>(text) {
+            Ok(entries) => entries,
+            Err(_) => serde_json::from_str::<BatchFile>(text)?.series,
+        },
+        _ => text
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(|url| BatchEntry {
+                url: url.to_string(),
+                ..Default::default()
+            })
+            .collect(),
+    };
+
+    Ok(entries)
+}
+
+fn entry_args(entry: &BatchEntry) -> anyhow::Result<Vec<String>> {
+    command_args(
+        &entry.url,
+        entry.kind,
+        entry.filter.as_deref(),
+        entry.format.as_deref(),
+        &[],
+    )
+}
+
+fn entry_app(app: &App, entry: &BatchEntry) -> anyhow::Result<App> {
+    let mut entry_app = sub_app(app, entry_args(entry)?)?;
+
+    if let Some(sub) = &entry.output {
+        entry_app.output_folder = PathBuf::from(&entry_app.output_folder)
+            .join(sub)
+            .to_string_lossy()
+            .into_owned();
+    }
+
+    Ok(entry_app)
+}
+
+// One limit per site and rate, shared by its entries, or --jobs would multiply the rate.
+// The rate is the one of each entry, with the config of its site applied
+fn share_limits<'a>(apps: impl Iterator<Item = &'a mut App>) {
+    let mut limits = HashMap::new();
+
+    for app in apps {
+        let Some(limit) = app.page_limit() else {
+            continue;
+        };
+        let site = host(app.command.url().unwrap_or_default()).to_string();
+        let rate = app.rate_limit.unwrap_or_default().to_bits();
+        app.shared_limit = Some(limits.entry((site, rate)).or_insert(limit).clone());
+    }
+}
+
+fn report(results: &[(String, anyhow::Result<()>)]) -> String {
+    let failed = results.iter().filter(|(_, r)| r.is_err()).count();
+    let mut buff = format!(
+        "Batch finished: {} succeeded, {} failed\n",
+        results.len() - failed,
+        failed
+    );
+
+    for (url, result) in results {
+        match result {
+            Ok(()) => buff.push_str(&format!("  ok      {}\n", url)),
+            Err(e) => buff.push_str(&format!("  failed  {}: {:#}\n", url, e)),
+        }
+    }
+
+    buff
+}
+
+/// `robin batch`: downloads every entry of a batch file, `jobs` series at a time.
+pub async fn batch_command(app: &App, file: &Path, jobs: usize) -> anyhow::Result<()> {
+    let text = fs::read_to_string(file)?;
+    let entries = parse_entries(file, &text)?;
+    println!("{} entries in {}", entries.len(), file.display());
+
+    let mut apps = entries
+        .iter()
+        .map(|entry| entry_app(app, entry))
+        .collect::<Vec<_>>();
+    share_limits(apps.iter_mut().flatten());
+
+    let results = futures::stream::iter(entries.iter().zip(apps).map(|(entry, app)| async move {
+        let result = match app {
+            Ok(app) => run(&app).await,
+            Err(e) => Err(e),
+        };
+        (entry.url.clone(), result)
+    }))
+    .buffered(jobs.max(1))
+    .collect::<Vec<_>>()
+    .await;
+
+    print!("\n{}", report(&results));
+
+    let failed = results.iter().filter(|(_, r)| r.is_err()).count();
+    if failed > 0 {
+        bail!("{} of {} entries failed", failed, results.len());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod batch_tests {
+    use super::*;
+    use anyhow::anyhow;
+    use clap::Parser;
+
+    #[test]
+    fn test_parse_entries() {
+        let list =
+            "# mirror\nhttps://shijiescans.com/series/uwu/\n\n  https://novelfull.com/owo.html\n";
+        let entries = parse_entries(Path::new("list.txt"), list).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].url, "https://novelfull.com/owo.html");
+
+        let toml = r#"
+            [[series]]
+            url = "https://shijiescans.com/series/uwu/"
+            filter = "latest:5"
+            format = "cbz"
+            output = "manga"
+        "#;
+        let entries = parse_entries(Path::new("list.toml"), toml).unwrap();
+        assert_eq!(entries[0].filter.as_deref(), Some("latest:5"));
+        assert_eq!(entries[0].output.as_deref(), Some("manga"));
+
+        let json = r#"[{"url": "https://example.com/x", "type": "novel", "format": "epub"}]"#;
+        let entries = parse_entries(Path::new("list.json"), json).unwrap();
+        assert_eq!(entries[0].kind, Some(ContentKind::Novel));
+
+        assert!(parse_entries(Path::new("list.json"), r#"[{"urll": "x"}]"#).is_err());
+    }
+
+    #[test]
+    fn test_entry_args() {
+        let entry = BatchEntry {
+            url: "https://shijiescans.com/series/uwu/".into(),
+            filter: Some("10:20".into()),
+            format: Some("cbz".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            entry_args(&entry).unwrap(),
+            [
+                "manga",
+                "https://shijiescans.com/series/uwu/",
+                "--filter",
+                "10:20",
+                "--archive",
+                "cbz"
+            ]
+        );
+        let app = App::parse_from(["robin", "-o", "/mirror", "info", "https://example.com"]);
+        assert!(sub_app(&app, entry_args(&entry).unwrap()).is_ok());
+
+        let entry = BatchEntry {
+            output: Some("manga".into()),
+            ..entry
+        };
+        assert_eq!(
+            entry_app(&app, &entry).unwrap().output_folder,
+            Path::new("/mirror").join("manga").to_string_lossy()
+        );
+
+        let entry = BatchEntry {
+            url: "https://example.com/x".into(),
+            ..Default::default()
+        };
+        assert!(entry_args(&entry).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_shared_limit() {
+        let mut app = App::parse_from(["robin", "info", "https://example.com"]);
+        app.settings = toml::from_str(
+            r#"
+            [sources."shijiescans.com"]
+            rate_limit = 20.0
+            "#,
+        )
+        .unwrap();
+        let entry = |url: &str| BatchEntry {
+            url: url.into(),
+            ..Default::default()
+        };
+
+        let mut apps = [
+            entry("https://shijiescans.com/series/uwu/"),
+            entry("https://shijiescans.com/series/owo/"),
+            entry("https://novelfull.com/uwu.html"),
+        ]
+        .iter()
+        .map(|e| entry_app(&app, e).unwrap())
+        .collect::<Vec<_>>();
+        share_limits(apps.iter_mut());
+        assert_eq!(apps[0].rate_limit, Some(20.0));
+        // No limit for novelfull.com in the config
+        assert!(apps[2].page_limit().is_none());
+
+        let start = tokio::time::Instant::now();
+        let waits = apps[..2].iter().map(|app| {
+            let limit = app.page_limit().unwrap();
+            async move {
+                limit.wait().await;
+                limit.wait().await;
+            }
+        });
+        futures::future::join_all(waits).await;
+
+        // Four requests on one schedule, not two on each
+        assert!(start.elapsed() >= std::time::Duration::from_millis(150));
+    }
+
+    #[test]
+    fn test_report() {
+        let results = vec![
+            ("https://a".to_string(), Ok(())),
+            ("https://b".to_string(), Err(anyhow!("Unsupported Source"))),
+        ];
+        assert_eq!(
+            report(&results),
+            "Batch finished: 1 succeeded, 1 failed\n  ok      https://a\n  failed  https://b: Unsupported Source\n"
+        );
+    }
+}
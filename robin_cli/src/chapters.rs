@@ -0,0 +1,231 @@
+use anyhow::bail;
+use chrono::NaiveDate;
+use robin_cli_core::chapter_check::check_chapters;
+use robin_cli_core::chapter_number::ChapterNumber;
+use robin_cli_core::matcher::{is_manga_url, is_novel_url, match_manga, match_novel};
+use robin_cli_core::selection::ChapterSelection;
+use robin_cli_core::sources::{Chapter, Novel, NovelChapter, Serie};
+use serde_json::{json, Value};
+
+use crate::args::App;
+
+#[derive(Debug, Clone, Copy)]
+pub enum ListFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+struct ChapterRow {
+    /// Position in the source list, from 1
+    index: usize,
+    number: ChapterNumber,
+    title: String,
+    date: Option<NaiveDate>,
+    url: String,
+}
+
+async fn find_rows(app: &App, url: &str) -> anyhow::Result<Vec<ChapterRow>> {
+    let rows = if is_manga_url(url) {
+        let mut source = match_manga(url.to_string(), app.net()).await?;
+        source.find_chapters().await;
+        source
+            .chapters()
+            .iter()
+            .enumerate()
+            .map(|(i, c)| ChapterRow {
+                index: i + 1,
+                number: c.number(),
+                title: c.title().trim().to_string(),
+                date: c.release_date(),
+                url: c.url().to_string(),
+            })
+            .collect()
+    } else if is_novel_url(url) {
+        let mut source = match_novel(url.to_string(), app.net()).await?;
+        source.find_chapters().await;
+        source
+            .chapters()
+            .iter()
+            .enumerate()
+            .map(|(i, c)| ChapterRow {
+                index: i + 1,
+                number: c.number(),
+                title: c.title().trim().to_string(),
+                date: c.release_date(),
+                url: c.url().to_string(),
+            })
+            .collect()
+    } else {
+        bail!("Unsupported Source");
+    };
+
+    Ok(rows)
+}
+
+fn date_string(date: Option<NaiveDate>) -> String {
+    date.map(|d| d.to_string()).unwrap_or_default()
+}
+
+fn to_table(rows: &[ChapterRow]) -> String {
+    let header = ["#", "Number", "Title", "Date", "URL"];
+    let cells = rows
+        .iter()
+        .map(|r| {
+            [
+                r.index.to_string(),
+                r.number.to_string(),
+                r.title.clone(),
+                date_string(r.date),
+                r.url.clone(),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    let mut widths = header.map(|h| h.chars().count());
+    for row in &cells {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let line = |row: &[String]| {
+        let mut line = format!("{:>w$}", row[0], w = widths[0]);
+        for (cell, width) in row.iter().zip(widths).skip(1) {
+            line.push_str(&format!("  {:<w$}", cell, w = width));
+        }
+        line.trim_end().to_string() + "\n"
+    };
+
+    let mut table = line(&header.map(String::from));
+    for row in &cells {
+        table.push_str(&line(row));
+    }
+
+    table
+}
+
+fn to_json(rows: &[ChapterRow]) -> Value {
+    rows.iter()
+        .map(|r| {
+            json!({
+                "index": r.index,
+                "number": r.number.value(),
+                "volume": r.number.volume,
+                "part": r.number.part,
+                "label": r.number.to_string(),
+                "title": r.title,
+                "date": r.date.map(|d| d.to_string()),
+                "url": r.url,
+            })
+        })
+        .collect()
+}
+
+fn csv_field(s: &str) -> String {
+    match s.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", s.replace('"', "\"\"")),
+        false => s.to_string(),
+    }
+}
+
+fn to_csv(rows: &[ChapterRow]) -> String {
+    let mut csv = String::from("index,number,volume,title,date,url\n");
+
+    for r in rows {
+        let fields = [
+            r.index.to_string(),
+            r.number.value().map(|n| n.to_string()).unwrap_or_default(),
+            r.number.volume.map(|n| n.to_string()).unwrap_or_default(),
+            r.title.clone(),
+            date_string(r.date),
+            r.url.clone(),
+        ];
+        let fields = fields.iter().map(|f| csv_field(f)).collect::<Vec<_>>();
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+
+    csv
+}
+
+/// `robin chapters`: lists the chapters of a serie, or checks the list with `--check`.
+pub async fn chapters_command(
+    app: &App,
+    url: &str,
+    filter: &ChapterSelection,
+    format: ListFormat,
+    check: bool,
+) -> anyhow::Result<()> {
+    let mut rows = find_rows(app, url).await?;
+    filter.apply(&mut rows, |r| r.number.value());
+
+    if check {
+        let chapters = rows
+            .iter()
+            .map(|r| (r.title.clone(), r.number))
+            .collect::<Vec<_>>();
+        let report = check_chapters(&chapters);
+
+        if report.is_clean() {
+            println!("{} chapters, no problems found.", rows.len());
+            return Ok(());
+        }
+
+        print!("{}", report);
+        bail!("the chapter list has problems");
+    }
+
+    match format {
+        ListFormat::Table => print!("{}", to_table(&rows)),
+        ListFormat::Json => println!("{}", serde_json::to_string_pretty(&to_json(&rows))?),
+        ListFormat::Csv => print!("{}", to_csv(&rows)),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod chapters_tests {
+    use super::*;
+
+    fn rows() -> Vec<ChapterRow> {
+        ["Bölüm 2", "Bölüm 1, \"Başlangıç\""]
+            .iter()
+            .enumerate()
+            .map(|(i, title)| ChapterRow {
+                index: i + 1,
+                number: ChapterNumber::parse(title, 2 - i),
+                title: title.to_string(),
+                date: NaiveDate::from_ymd_opt(2024, 3, 12 - i as u32),
+                url: format!("https://example.com/{}", 2 - i),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_table() {
+        assert_eq!(
+            to_table(&rows()),
+            "#  Number  Title                 Date        URL\n\
+             1  Ch. 2   Bölüm 2               2024-03-12  https://example.com/2\n\
+             2  Ch. 1   Bölüm 1, \"Başlangıç\"  2024-03-11  https://example.com/1\n"
+        );
+    }
+
+    #[test]
+    fn test_csv_and_json() {
+        assert_eq!(
+            to_csv(&rows()),
+            "index,number,volume,title,date,url\n\
+             1,2,,Bölüm 2,2024-03-12,https://example.com/2\n\
+             2,1,,\"Bölüm 1, \"\"Başlangıç\"\"\",2024-03-11,https://example.com/1\n"
+        );
+
+        let json = to_json(&rows());
+        assert_eq!(json[1]["number"], 1.0);
+        assert_eq!(json[1]["label"], "Ch. 1");
+        assert_eq!(json[1]["volume"], Value::Null);
+        assert_eq!(json[0]["date"], "2024-03-12");
+    }
+}
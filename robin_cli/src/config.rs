@@ -0,0 +1,538 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+use anyhow::{anyhow, bail, Context};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, ValueEnum};
+use robin_cli_core::naming::NameTemplate;
+use robin_cli_core::net::check_rate;
+use serde::Deserialize;
+
+use crate::args::{App, ArchiveArg, Commands, NovelFormat, PageFormat};
+
+/// Options of the config file, under `[defaults]` or `[sources."<site>"]`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    pub output: Option<String>,
+    pub concurrency: Option<usize>,
+    pub proxy: Option<String>,
+    pub user_agent: Option<String>,
+    /// Page requests per second
+    pub rate_limit: Option<f64>,
+    pub temp_dir: Option<String>,
+    pub series_template: Option<String>,
+    pub chapter_template: Option<String>,
+    pub page_template: Option<String>,
+    /// Archive format for manga, `folder` for none
+    pub manga_format: Option<String>,
+    /// txt or epub
+    pub novel_format: Option<String>,
+    // Cleaning rules, same as the `--drop-credits` options
+    pub drop_credits: Option<bool>,
+    pub credit_min_chapters: Option<usize>,
+    pub credit_blocklist: Option<String>,
+    // Image processing, same as the `manga` options
+    pub convert_to: Option<String>,
+    pub max_width: Option<u32>,
+    pub max_height: Option<u32>,
+    pub grayscale: Option<bool>,
+    pub jpeg_quality: Option<u8>,
+}
+
+impl Settings {
+    /// These settings with the ones set in `over` replaced.
+    fn overlay(self, over: &Settings) -> Settings {
+        let over = over.clone();
+        Settings {
+            output: over.output.or(self.output),
+            concurrency: over.concurrency.or(self.concurrency),
+            proxy: over.proxy.or(self.proxy),
+            user_agent: over.user_agent.or(self.user_agent),
+            rate_limit: over.rate_limit.or(self.rate_limit),
+            temp_dir: over.temp_dir.or(self.temp_dir),
+            series_template: over.series_template.or(self.series_template),
+            chapter_template: over.chapter_template.or(self.chapter_template),
+            page_template: over.page_template.or(self.page_template),
+            manga_format: over.manga_format.or(self.manga_format),
+            novel_format: over.novel_format.or(self.novel_format),
+            drop_credits: over.drop_credits.or(self.drop_credits),
+            credit_min_chapters: over.credit_min_chapters.or(self.credit_min_chapters),
+            credit_blocklist: over.credit_blocklist.or(self.credit_blocklist),
+            convert_to: over.convert_to.or(self.convert_to),
+            max_width: over.max_width.or(self.max_width),
+            max_height: over.max_height.or(self.max_height),
+            grayscale: over.grayscale.or(self.grayscale),
+            jpeg_quality: over.jpeg_quality.or(self.jpeg_quality),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub defaults: Settings,
+    /// By site name, `shijiescans.com` also covers its subdomains
+    #[serde(default)]
+    pub sources: BTreeMap<String, Settings>,
+    /// Picked with `--profile`, on top of the built-in profile of the same name
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Settings>,
+}
+
+/// Built-in `--profile`s.
+fn builtin_profile(name: &str) -> Option<Settings> {
+    match name {
+        // Paperwhite sized grayscale JPEG pages, sorted by chapter number
+        "kindle" => Some(Settings {
+            chapter_template: Some("{chapter:04.1} {chapter_title}".into()),
+            manga_format: Some("cbz".into()),
+            novel_format: Some("epub".into()),
+            convert_to: Some("jpeg".into()),
+            max_width: Some(1072),
+            max_height: Some(1448),
+            grayscale: Some(true),
+            jpeg_quality: Some(85),
+            ..Default::default()
+        }),
+        // Untouched pages with the source in the names, for keeping
+        "archive" => Some(Settings {
+            series_template: Some("{title} [{source}]".into()),
+            chapter_template: Some("{chapter:04.1} {chapter_title}".into()),
+            manga_format: Some("cbz".into()),
+            novel_format: Some("txt".into()),
+            drop_credits: Some(false),
+            ..Default::default()
+        }),
+        _ => None,
+    }
+}
+
+/// `$XDG_CONFIG_HOME/robin/config.toml`, `~/.config` without XDG.
+pub fn default_path() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))
+        .map(|dir| dir.join("robin").join("config.toml"))
+}
+
+/// Folder of the cookie jar and other state kept between runs.
+pub fn default_data_dir() -> Option<PathBuf> {
+    env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))
+        .map(|dir| dir.join("robin"))
+}
+
+pub(crate) fn host(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let host = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    host.strip_prefix("www.").unwrap_or(host)
+}
+
+// Paths in the config can start with ~/
+fn expand_home(path: &str) -> String {
+    match (path.strip_prefix("~/"), env::var("HOME")) {
+        (Some(rest), Ok(home)) => Path::new(&home).join(rest).to_string_lossy().into_owned(),
+        _ => path.to_string(),
+    }
+}
+
+impl Config {
+    /// The file given with `--config`, or the default one if it exists.
+    pub fn load(path: Option<&Path>) -> anyhow::Result<Config> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match default_path() {
+                Some(path) if path.exists() => path,
+                _ => return Ok(Config::default()),
+            },
+        };
+
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("can't read config file {}", path.display()))?;
+        toml::from_str(&text).with_context(|| format!("bad config file {}", path.display()))
+    }
+
+    /// The defaults with the settings of the URL's site on top, then the profile.
+    pub fn for_url(&self, url: Option<&str>, profile: Option<&str>) -> anyhow::Result<Settings> {
+        let host = url.map(host).unwrap_or_default();
+
+        let settings = self
+            .sources
+            .iter()
+            .filter(|(site, _)| host == site.as_str() || host.ends_with(&format!(".{}", site)))
+            .fold(self.defaults.clone(), |settings, (_, over)| {
+                settings.overlay(over)
+            });
+
+        let Some(name) = profile else {
+            return Ok(settings);
+        };
+        let profile = match (builtin_profile(name), self.profiles.get(name)) {
+            (None, None) => bail!("no profile named \"{}\"", name),
+            (builtin, custom) => builtin
+                .unwrap_or_default()
+                .overlay(&custom.cloned().unwrap_or_default()),
+        };
+
+        Ok(settings.overlay(&profile))
+    }
+}
+
+// Global options the config can set, as clap ids
+const GLOBAL_IDS: [&str; 9] = [
+    "output_folder",
+    "concurrent_chapters",
+    "proxy",
+    "user_agent",
+    "rate_limit",
+    "temp_dir",
+    "series_template",
+    "chapter_template",
+    "page_template",
+];
+
+fn is_explicit(matches: &ArgMatches, id: &str) -> bool {
+    matches!(
+        matches.value_source(id),
+        Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+    )
+}
+
+/// Global options given on the command line, the config doesn't touch them.
+pub fn explicit_ids(matches: &ArgMatches) -> Vec<String> {
+    GLOBAL_IDS
+        .iter()
+        .filter(|id| is_explicit(matches, id))
+        .map(|id| id.to_string())
+        .collect()
+}
+
+fn parse_value<T: ValueEnum>(value: &str) -> anyhow::Result<T> {
+    T::from_str(value, true).map_err(|e| anyhow!("config: {}", e))
+}
+
+/// Fills what wasn't given on the command line from `app.settings`, with the
+/// per-source settings of the command URL. `sub` are the subcommand matches.
+pub fn apply_config(app: &mut App, sub: Option<&ArgMatches>) -> anyhow::Result<()> {
+    let settings = app
+        .settings
+        .for_url(app.command.url(), app.profile.as_deref())?;
+    let explicit = app.explicit.clone();
+    let unset = |id: &str| !explicit.iter().any(|e| e == id);
+
+    if let (true, Some(output)) = (unset("output_folder"), &settings.output) {
+        app.output_folder = expand_home(output);
+    }
+    if let (true, Some(concurrency)) = (unset("concurrent_chapters"), settings.concurrency) {
+        app.concurrent_chapters = concurrency;
+    }
+    if let (true, Some(proxy)) = (unset("proxy"), &settings.proxy) {
+        app.proxy = proxy.clone();
+    }
+    if let (true, Some(user_agent)) = (unset("user_agent"), &settings.user_agent) {
+        app.user_agent = Some(user_agent.clone());
+    }
+    if let (true, Some(rate_limit)) = (unset("rate_limit"), settings.rate_limit) {
+        app.rate_limit = Some(check_rate(rate_limit).map_err(|e| anyhow!("config: {}", e))?);
+    }
+    if let (true, Some(temp_dir)) = (unset("temp_dir"), &settings.temp_dir) {
+        app.temp_dir = Some(PathBuf::from(expand_home(temp_dir)));
+    }
+    if let (true, Some(template)) = (unset("series_template"), &settings.series_template) {
+        app.series_template = NameTemplate::parse(template)?;
+    }
+    if let (true, Some(template)) = (unset("chapter_template"), &settings.chapter_template) {
+        app.chapter_template = NameTemplate::parse(template)?;
+    }
+    if let (true, Some(template)) = (unset("page_template"), &settings.page_template) {
+        app.page_template = NameTemplate::parse(template)?;
+    }
+
+    let Some(sub) = sub else {
+        return Ok(());
+    };
+
+    match &mut app.command {
+        Commands::Manga {
+            archive,
+            drop_credits,
+            credit_min_chapters,
+            credit_blocklist,
+            convert_to,
+            max_width,
+            max_height,
+            grayscale,
+            jpeg_quality,
+            ..
+        } => {
+            if let (false, Some(format)) = (is_explicit(sub, "convert_to"), &settings.convert_to) {
+                *convert_to = Some(parse_value::<PageFormat>(format)?);
+            }
+            if let (false, Some(width)) = (is_explicit(sub, "max_width"), settings.max_width) {
+                *max_width = Some(width);
+            }
+            if let (false, Some(height)) = (is_explicit(sub, "max_height"), settings.max_height) {
+                *max_height = Some(height);
+            }
+            if let (false, Some(gray)) = (is_explicit(sub, "grayscale"), settings.grayscale) {
+                *grayscale = gray;
+            }
+            if let (false, Some(quality)) =
+                (is_explicit(sub, "jpeg_quality"), settings.jpeg_quality)
+            {
+                *jpeg_quality = Some(quality);
+            }
+            if let (false, Some(format)) = (is_explicit(sub, "archive"), &settings.manga_format) {
+                *archive = match format.as_str() {
+                    "folder" => None,
+                    format => Some(parse_value::<ArchiveArg>(format)?),
+                };
+            }
+            if let (false, Some(drop)) = (is_explicit(sub, "drop_credits"), settings.drop_credits) {
+                *drop_credits = drop;
+            }
+            if let (false, Some(min)) = (
+                is_explicit(sub, "credit_min_chapters"),
+                settings.credit_min_chapters,
+            ) {
+                *credit_min_chapters = min;
+            }
+            if let (false, Some(blocklist)) = (
+                is_explicit(sub, "credit_blocklist"),
+                &settings.credit_blocklist,
+            ) {
+                *credit_blocklist = Some(PathBuf::from(expand_home(blocklist)));
+            }
+        }
+        Commands::Novel { format, .. } => {
+            if let (false, Some(novel_format)) =
+                (is_explicit(sub, "format"), &settings.novel_format)
+            {
+                *format = parse_value::<NovelFormat>(novel_format)?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod config_tests {
+    use super::*;
+    use clap::{CommandFactory, FromArgMatches};
+
+    const CONFIG: &str = r#"
+        [defaults]
+        output = "/mirror"
+        concurrency = 4
+        user_agent = "robin"
+        rate_limit = 5.0
+        manga_format = "cbz"
+
+        [sources."shijiescans.com"]
+        rate_limit = 0.5
+        manga_format = "folder"
+    "#;
+
+    fn app_from(command: clap::Command, args: &[&str]) -> App {
+        let matches = command.get_matches_from(args);
+        let mut app = App::from_arg_matches(&matches).unwrap();
+        app.settings = toml::from_str(CONFIG).unwrap();
+        app.explicit = explicit_ids(&matches);
+        apply_config(&mut app, matches.subcommand().map(|(_, m)| m)).unwrap();
+        app
+    }
+
+    // Leaves out the environment of whoever runs the tests, see `test_env_over_config`
+    fn parse_app(args: &[&str]) -> App {
+        app_from(App::command().mut_args(|a| a.env(None)), args)
+    }
+
+    #[test]
+    fn test_for_url() {
+        let config: Config = toml::from_str(CONFIG).unwrap();
+        assert_eq!(config.for_url(None, None).unwrap().rate_limit, Some(5.0));
+        assert_eq!(
+            config
+                .for_url(Some("https://www.shijiescans.com/series/uwu/"), None)
+                .unwrap()
+                .rate_limit,
+            Some(0.5)
+        );
+        assert_eq!(
+            config
+                .for_url(Some("https://cdn.shijiescans.com/x"), None)
+                .unwrap()
+                .concurrency,
+            Some(4)
+        );
+        assert_eq!(
+            config
+                .for_url(Some("https://notshijiescans.com/"), None)
+                .unwrap()
+                .rate_limit,
+            Some(5.0)
+        );
+
+        assert!(toml::from_str::<Config>("[defaults]\noutptu = \"x\"").is_err());
+    }
+
+    #[test]
+    fn test_profiles() {
+        let config: Config = toml::from_str(
+            r#"
+            [defaults]
+            manga_format = "zip"
+
+            [profiles.kindle]
+            max_width = 758
+
+            [profiles.phone]
+            max_width = 1080
+            "#,
+        )
+        .unwrap();
+
+        let kindle = config.for_url(None, Some("kindle")).unwrap();
+        assert_eq!(kindle.max_width, Some(758));
+        assert_eq!(kindle.grayscale, Some(true));
+        assert_eq!(kindle.manga_format.as_deref(), Some("cbz"));
+
+        let phone = config.for_url(None, Some("phone")).unwrap();
+        assert_eq!(phone.max_width, Some(1080));
+        assert_eq!(phone.manga_format.as_deref(), Some("zip"));
+
+        assert!(config.for_url(None, Some("kobo")).is_err());
+
+        let app = parse_app(&[
+            "robin",
+            "--profile",
+            "kindle",
+            "manga",
+            "https://example.com/uwu",
+            "--max-height",
+            "1000",
+        ]);
+        assert_eq!(
+            app.chapter_template.to_string(),
+            "{chapter:04.1} {chapter_title}"
+        );
+        assert!(matches!(
+            app.command,
+            Commands::Manga {
+                grayscale: true,
+                max_height: Some(1000),
+                convert_to: Some(PageFormat::Jpeg),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_env_over_config() {
+        // The environment is set for a child process running this test, setting it here
+        // would leak into the tests running alongside
+        if env::var_os("ROBIN_TEST_ENV_CHILD").is_none() {
+            let status = std::process::Command::new(env::current_exe().unwrap())
+                .args(["config::config_tests::test_env_over_config", "--exact"])
+                .env("ROBIN_TEST_ENV_CHILD", "1")
+                .env("ROBIN_USER_AGENT", "uwu/1.0")
+                .env_remove("ROBIN_OUTPUT_FOLDER")
+                .env_remove("ROBIN_PROFILE")
+                .status()
+                .unwrap();
+            assert!(status.success());
+            return;
+        }
+
+        let app = app_from(
+            App::command(),
+            &["robin", "manga", "https://example.com/uwu"],
+        );
+        assert_eq!(app.user_agent.as_deref(), Some("uwu/1.0"));
+        assert!(app.explicit.contains(&"user_agent".to_string()));
+        assert_eq!(app.output_folder, "/mirror");
+
+        let app = app_from(
+            App::command(),
+            &[
+                "robin",
+                "--user-agent",
+                "owo/2.0",
+                "manga",
+                "https://example.com/uwu",
+            ],
+        );
+        assert_eq!(app.user_agent.as_deref(), Some("owo/2.0"));
+    }
+
+    #[test]
+    fn test_rate_limit_range() {
+        let command = || App::command().mut_args(|a| a.env(None));
+        let args = [
+            "robin",
+            "--rate-limit",
+            "1e-300",
+            "info",
+            "https://example.com",
+        ];
+        assert!(command().try_get_matches_from(args).is_err());
+
+        let matches = command().get_matches_from(["robin", "info", "https://example.com"]);
+        let mut app = App::from_arg_matches(&matches).unwrap();
+        app.settings = toml::from_str("[defaults]\nrate_limit = 1e-300").unwrap();
+        assert!(apply_config(&mut app, None).is_err());
+    }
+
+    #[test]
+    fn test_flags_over_config() {
+        let app = parse_app(&["robin", "manga", "https://example.com/uwu"]);
+        assert_eq!(app.output_folder, "/mirror");
+        assert_eq!(app.concurrent_chapters, 4);
+        assert!(matches!(
+            app.command,
+            Commands::Manga {
+                archive: Some(ArchiveArg::Cbz),
+                ..
+            }
+        ));
+
+        let app = parse_app(&[
+            "robin",
+            "-c",
+            "2",
+            "manga",
+            "https://shijiescans.com/series/uwu/",
+        ]);
+        assert_eq!(app.concurrent_chapters, 2);
+        assert_eq!(app.rate_limit, Some(0.5));
+        assert!(matches!(app.command, Commands::Manga { archive: None, .. }));
+
+        let app = parse_app(&[
+            "robin",
+            "--rate-limit",
+            "1",
+            "manga",
+            "https://shijiescans.com/series/uwu/",
+            "--archive",
+            "zip",
+        ]);
+        assert_eq!(app.rate_limit, Some(1.0));
+        assert!(matches!(
+            app.command,
+            Commands::Manga {
+                archive: Some(ArchiveArg::Zip),
+                ..
+            }
+        ));
+    }
+}
@@ -0,0 +1,137 @@
+use std::fs::read_to_string;
+use std::io::Write;
+
+use anyhow::{bail, Context};
+use chrono::DateTime;
+use robin_cli_core::cookies::{
+    clear, create_private, import, jar_cookies, load_jar, parse_cookies, save_jar, to_netscape,
+    ExportedCookie,
+};
+
+use crate::args::{App, CookiesAction};
+
+// Values stay out of the list, they are as good as a password
+fn format_list(cookies: &[ExportedCookie]) -> String {
+    let domain_width = cookies.iter().map(|c| c.domain.len()).max().unwrap_or(0);
+    let name_width = cookies.iter().map(|c| c.name.len()).max().unwrap_or(0);
+
+    cookies
+        .iter()
+        .map(|c| {
+            let expires = c
+                .expires
+                .and_then(|e| DateTime::from_timestamp(e, 0))
+                .map_or("session".to_string(), |e| e.format("%Y-%m-%d").to_string());
+
+            format!(
+                "{:domain_width$}  {:name_width$}  {}  {}\n",
+                c.domain, c.name, c.path, expires
+            )
+        })
+        .collect()
+}
+
+/// `robin cookies`: edits the jar in the data folder.
+pub fn cookies_command(app: &App, action: &CookiesAction) -> anyhow::Result<()> {
+    let Some(path) = app.jar_path() else {
+        bail!("No data folder found, use --data-dir");
+    };
+    let mut store = load_jar(&path)?;
+
+    match action {
+        CookiesAction::Import { file, domain } => {
+            let text =
+                read_to_string(file).with_context(|| format!("can't read {}", file.display()))?;
+            let cookies = parse_cookies(&text).with_context(|| {
+                format!("{} is not a cookies.txt or JSON export", file.display())
+            })?;
+
+            let count = import(&mut store, &cookies, domain.as_deref())?;
+            save_jar(&store, &path)?;
+            println!("Imported {} cookies into {}", count, path.display());
+        }
+        CookiesAction::List { domain } => {
+            let cookies = jar_cookies(&store, domain.as_deref());
+            match cookies.is_empty() {
+                true => println!("No cookies."),
+                false => print!("{}", format_list(&cookies)),
+            }
+        }
+        CookiesAction::Export { file, domain } => {
+            let cookies = jar_cookies(&store, domain.as_deref());
+            create_private(file)
+                .and_then(|mut f| f.write_all(to_netscape(&cookies).as_bytes()))
+                .with_context(|| format!("can't write {}", file.display()))?;
+            println!("Exported {} cookies to {}", cookies.len(), file.display());
+        }
+        CookiesAction::Clear { domain } => {
+            let count = clear(&mut store, domain.as_deref());
+            save_jar(&store, &path)?;
+            println!("Removed {} cookies", count);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod cookies_tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_format_list() {
+        let cookie = ExportedCookie {
+            domain: "shijiescans.com".to_string(),
+            include_subdomains: true,
+            path: "/".to_string(),
+            secure: true,
+            http_only: false,
+            expires: Some(4102444800),
+            name: "cf_clearance".to_string(),
+            value: "secret".to_string(),
+        };
+        let session = ExportedCookie {
+            domain: "novelfull.com".to_string(),
+            expires: None,
+            name: "sid".to_string(),
+            ..cookie.clone()
+        };
+
+        let list = format_list(&[cookie, session]);
+        assert_eq!(
+            list,
+            "shijiescans.com  cf_clearance  /  2100-01-01\nnovelfull.com    sid           /  session\n"
+        );
+        assert!(!list.contains("secret"));
+    }
+
+    #[test]
+    fn test_import_and_clear() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("cookies.txt");
+        std::fs::write(
+            &file,
+            ".shijiescans.com\tTRUE\t/\tTRUE\t4102444800\tcf_clearance\tabc\n",
+        )
+        .unwrap();
+
+        let data_dir = dir.path().join("data").to_string_lossy().into_owned();
+        let file = file.to_string_lossy().into_owned();
+        let app = App::parse_from(["robin", "--data-dir", &data_dir, "cookies", "import", &file]);
+        let crate::args::Commands::Cookies { action } = &app.command else {
+            panic!("not a cookies command");
+        };
+        cookies_command(&app, action).unwrap();
+
+        let path = app.jar_path().unwrap();
+        assert_eq!(jar_cookies(&load_jar(&path).unwrap(), None).len(), 1);
+
+        let app = App::parse_from(["robin", "--data-dir", &data_dir, "cookies", "clear"]);
+        let crate::args::Commands::Cookies { action } = &app.command else {
+            panic!("not a cookies command");
+        };
+        cookies_command(&app, action).unwrap();
+        assert!(jar_cookies(&load_jar(&path).unwrap(), None).is_empty());
+    }
+}
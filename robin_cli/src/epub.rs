@@ -0,0 +1,96 @@
+use std::fs::{self, rename, File};
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDate;
+use epub_builder::{EpubBuilder, EpubContent, EpubVersion, ReferenceType, ZipLibrary};
+use robin_cli_core::filetype::mime_type;
+
+// EPUB has no per-chapter date, so it goes on top of the chapter text
+fn chapter_body(contents: &str, released: Option<NaiveDate>) -> String {
+    let date = released.map(|d| format!("<p><time datetime=\"{d}\">{d}</time></p>\n"));
+
+    let paragraphs = contents
+        .split("\n")
+        .map(|p| format!("<p>{}</p>", p))
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    date.unwrap_or_default() + &paragraphs
+}
+
+/// Builds an ebook from chapter text files and their release dates, in the given order.
+/// The book is dated with its latest chapter.
+pub fn write_epub(
+    title: &str,
+    author: &str,
+    cover: &Path,
+    chapters: &[(PathBuf, Option<NaiveDate>)],
+    destination: &Path,
+    mut on_chapter: impl FnMut(),
+) -> anyhow::Result<()> {
+    let cover_bytes = fs::read(cover)?;
+    let cover_name = cover.file_name().unwrap_or_default().to_string_lossy();
+    let cover_ext = cover_name
+        .rsplit_once('.')
+        .map(|(_, ext)| ext)
+        .unwrap_or("jpg");
+    let cover_mimetype = mime_type(cover_ext).unwrap_or("image/jpeg");
+
+    let mut book_builder = EpubBuilder::new(ZipLibrary::new().unwrap()).unwrap();
+
+    book_builder
+        .epub_version(EpubVersion::V30)
+        .metadata("title", title)
+        .unwrap()
+        .metadata("author", author)
+        .unwrap()
+        .add_cover_image(cover_name.as_ref(), cover_bytes.as_slice(), cover_mimetype)
+        .unwrap();
+
+    if let Some(date) = chapters.iter().filter_map(|(_, date)| *date).max() {
+        book_builder.set_publication_date(date.and_time(Default::default()).and_utc());
+    }
+
+    for (path, released) in chapters {
+        let contents = fs::read_to_string(path)?;
+        let ch_name = path.file_stem().unwrap_or_default().to_string_lossy();
+
+        let paragraphs = chapter_body(&contents, *released);
+
+        let epub_content = EpubContent::new(format!("{}.xhtml", ch_name), paragraphs.as_bytes())
+            .title(ch_name)
+            .reftype(ReferenceType::Text);
+
+        book_builder.add_content(epub_content).unwrap();
+
+        on_chapter();
+    }
+
+    book_builder.inline_toc();
+
+    let mut partial = destination.as_os_str().to_owned();
+    partial.push(".part");
+    let partial = PathBuf::from(partial);
+
+    let mut f = File::create(&partial)?;
+    book_builder.generate(&mut f).unwrap();
+    rename(&partial, destination)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod epub_tests {
+    use super::*;
+
+    #[test]
+    fn test_chapter_body() {
+        assert_eq!(chapter_body("uwu\nowo", None), "<p>uwu</p>\n<p>owo</p>");
+
+        let released = NaiveDate::from_ymd_opt(2024, 3, 12);
+        assert_eq!(
+            chapter_body("uwu", released),
+            "<p><time datetime=\"2024-03-12\">2024-03-12</time></p>\n<p>uwu</p>"
+        );
+    }
+}
@@ -0,0 +1,141 @@
+use anyhow::{anyhow, bail};
+use clap::{CommandFactory, FromArgMatches, Parser};
+use robin_cli_core::matcher::content_kind;
+use robin_cli_core::sources::ContentKind;
+
+use crate::args::{App, Commands};
+use crate::config::apply_config;
+use crate::run;
+
+// Only the subcommand, the global options come from the outer command line
+#[derive(Parser)]
+#[command(no_binary_name = true)]
+struct SubCommand {
+    #[command(subcommand)]
+    command: Commands,
+}
+
+/// The `manga`/`novel` command line for a URL, as it would be typed after `robin`.
+/// `kind` comes from the source when missing. For manga `format` is the archive
+/// format (`folder` for none), for novels txt or epub.
+pub fn command_args(
+    url: &str,
+    kind: Option<ContentKind>,
+    filter: Option<&str>,
+    format: Option<&str>,
+    extra: &[String],
+) -> anyhow::Result<Vec<String>> {
+    let Some(kind) = kind.or_else(|| content_kind(url)) else {
+        bail!("Unsupported Source");
+    };
+
+    let mut args = match kind {
+        ContentKind::Manga => vec!["manga".to_string(), url.to_string()],
+        ContentKind::Novel => vec!["novel".to_string(), url.to_string()],
+    };
+
+    if let Some(filter) = filter {
+        args.extend(["--filter".to_string(), filter.to_string()]);
+    }
+
+    match (kind, format) {
+        (_, None) | (ContentKind::Manga, Some("folder")) => {}
+        (ContentKind::Manga, Some(format)) => args.extend(["--archive".into(), format.into()]),
+        (ContentKind::Novel, Some(format)) => args.extend(["--format".into(), format.into()]),
+    }
+
+    args.extend(extra.iter().cloned());
+
+    Ok(args)
+}
+
+/// `app` running a command line from `command_args` instead, parsed with the same
+/// rules as the real one and with the config settings of its URL.
+pub fn sub_app(app: &App, args: Vec<String>) -> anyhow::Result<App> {
+    let matches = SubCommand::command()
+        .try_get_matches_from(args)
+        .map_err(|e| anyhow!(e.render().to_string().trim().to_string()))?;
+
+    let mut sub_app = App {
+        command: SubCommand::from_arg_matches(&matches)?.command,
+        ..app.clone()
+    };
+    apply_config(&mut sub_app, matches.subcommand().map(|(_, m)| m))?;
+
+    Ok(sub_app)
+}
+
+fn default_format(kind: ContentKind) -> &'static str {
+    match kind {
+        ContentKind::Manga => "cbz",
+        ContentKind::Novel => "epub",
+    }
+}
+
+/// `robin get`: `manga` or `novel` depending on the source, as cbz or epub unless told otherwise.
+pub async fn get_command(
+    app: &App,
+    url: &str,
+    format: Option<&str>,
+    extra: &[String],
+) -> anyhow::Result<()> {
+    let Some(kind) = content_kind(url) else {
+        bail!("Unsupported Source");
+    };
+
+    // The config formats are used by the commands anyway, the get defaults only without them
+    let settings = app.settings.for_url(Some(url), app.profile.as_deref())?;
+    let config_format = match kind {
+        ContentKind::Manga => settings.manga_format,
+        ContentKind::Novel => settings.novel_format,
+    };
+    let format = format
+        .map(String::from)
+        .or(config_format)
+        .unwrap_or(default_format(kind).to_string());
+
+    let args = command_args(url, Some(kind), None, Some(&format), extra)?;
+    run(&sub_app(app, args)?).await
+}
+
+#[cfg(test)]
+mod get_tests {
+    use super::*;
+
+    fn parse_command(args: Vec<String>) -> anyhow::Result<Commands> {
+        let app = App::parse_from(["robin", "info", "https://example.com"]);
+        Ok(sub_app(&app, args)?.command)
+    }
+
+    #[test]
+    fn test_command_args() {
+        let url = "https://novelfull.com/uwu.html";
+        let args = command_args(
+            url,
+            None,
+            None,
+            Some(default_format(ContentKind::Novel)),
+            &[],
+        )
+        .unwrap();
+        assert_eq!(args, ["novel", url, "--format", "epub"]);
+        assert!(matches!(
+            parse_command(args).unwrap(),
+            Commands::Novel { .. }
+        ));
+
+        let url = "https://shijiescans.com/series/uwu/";
+        let extra = ["--grayscale".to_string()];
+        let args = command_args(url, None, Some("latest:5"), Some("folder"), &extra).unwrap();
+        assert_eq!(args, ["manga", url, "--filter", "latest:5", "--grayscale"]);
+        assert!(matches!(
+            parse_command(args).unwrap(),
+            Commands::Manga { archive: None, .. }
+        ));
+
+        let args = command_args(url, None, None, Some("rar"), &[]).unwrap();
+        assert!(parse_command(args).is_err());
+
+        assert!(command_args("https://example.com/uwu", None, None, None, &[]).is_err());
+    }
+}
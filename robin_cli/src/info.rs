@@ -0,0 +1,131 @@
+use anyhow::bail;
+use robin_cli_core::matcher::{is_manga_url, is_novel_url, match_manga, match_novel};
+use robin_cli_core::sources::{Novel, Serie};
+use robin_cli_core::utils::capitalize;
+use serde_json::{json, Map, Value};
+
+use crate::args::App;
+
+struct SerieInfo {
+    kind: &'static str,
+    url: String,
+    site: String,
+    lang: String,
+    fields: Vec<(String, String)>,
+    chapter_count: usize,
+}
+
+// The chapter count is kept apart, not every source has it in `info`
+fn to_fields(info: Vec<(&str, String)>) -> Vec<(String, String)> {
+    info.into_iter()
+        .filter(|(key, _)| *key != "chapter count")
+        .map(|(key, value)| (key.to_string(), value.trim().to_string()))
+        .collect()
+}
+
+async fn fetch_info(app: &App, url: &str) -> anyhow::Result<SerieInfo> {
+    if is_manga_url(url) {
+        let mut source = match_manga(url.to_string(), app.net()).await?;
+        source.find_chapters().await;
+
+        Ok(SerieInfo {
+            kind: "manga",
+            url: url.to_string(),
+            site: source.source_name().to_string(),
+            lang: source.lang().to_string(),
+            fields: to_fields(source.info()),
+            chapter_count: source.chapter_count(),
+        })
+    } else if is_novel_url(url) {
+        let mut source = match_novel(url.to_string(), app.net()).await?;
+        source.find_chapters().await;
+        let chapter_count = source.chapters().len();
+
+        Ok(SerieInfo {
+            kind: "novel",
+            url: url.to_string(),
+            site: source.source_name().to_string(),
+            lang: source.lang().to_string(),
+            fields: to_fields(source.info()),
+            chapter_count,
+        })
+    } else {
+        bail!("Unsupported Source");
+    }
+}
+
+fn to_json(info: &SerieInfo) -> Value {
+    let fields = info
+        .fields
+        .iter()
+        .map(|(key, value)| (key.replace(' ', "_"), Value::String(value.clone())))
+        .collect::<Map<String, Value>>();
+
+    json!({
+        "kind": info.kind,
+        "url": info.url,
+        "site": info.site,
+        "lang": info.lang,
+        "info": fields,
+        "chapter_count": info.chapter_count,
+    })
+}
+
+fn to_text(info: &SerieInfo) -> String {
+    let mut buff = format!(
+        "Kind: {}\nSite: {}\nLang: {}\n",
+        info.kind, info.site, info.lang
+    );
+
+    for (key, value) in &info.fields {
+        buff.push_str(&format!("{}: {}\n", capitalize(key), value));
+    }
+    buff.push_str(&format!("Chapter count: {}\n", info.chapter_count));
+
+    buff
+}
+
+/// `robin info`: prints the metadata of a serie without downloading it.
+pub async fn info_command(app: &App, url: &str, json: bool) -> anyhow::Result<()> {
+    let info = fetch_info(app, url).await?;
+
+    match json {
+        true => println!("{}", serde_json::to_string_pretty(&to_json(&info))?),
+        false => print!("{}", to_text(&info)),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod info_tests {
+    use super::*;
+
+    #[test]
+    fn test_output() {
+        let info = SerieInfo {
+            kind: "manga",
+            url: "https://shijiescans.com/series/uwu".into(),
+            site: "shijiescans.com".into(),
+            lang: "tr".into(),
+            fields: to_fields(vec![
+                ("title", " Uwu ".into()),
+                ("first chapter", "Bölüm 1".into()),
+                ("chapter count", "0".into()),
+            ]),
+            chapter_count: 12,
+        };
+
+        let json = to_json(&info);
+        assert_eq!(json["info"]["title"], "Uwu");
+        assert_eq!(json["info"]["first_chapter"], "Bölüm 1");
+        assert_eq!(json["info"].get("chapter_count"), None);
+        assert_eq!(json["chapter_count"], 12);
+
+        assert_eq!(
+            to_text(&info),
+            "Kind: manga\nSite: shijiescans.com\nLang: tr\nTitle: Uwu\n\
+             First chapter: Bölüm 1\nChapter count: 12\n"
+        );
+    }
+}
@@ -1,41 +1,173 @@
-use std::fs::{create_dir_all, File};
-use std::io::{Read, Write};
+use std::fs::{create_dir_all, remove_dir_all};
 use std::path::PathBuf;
-
-use clap::Parser;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail};
+use clap::{CommandFactory, FromArgMatches};
+use robin_cli_core::chapter_number::volume_label;
+use robin_cli_core::cookies::{load_jar, merge_jar};
+use robin_cli_core::credits::{drop_credits_in_serie, load_blocklist, CreditAction, CreditOptions};
+use robin_cli_core::manifest::Manifest;
 use robin_cli_core::matcher::{match_manga, match_novel};
-use robin_cli_core::sources::{Novel, Serie};
+use robin_cli_core::naming::{today, NameContext};
+use robin_cli_core::net::CookieStoreMutex;
+use robin_cli_core::path_safety::sanitize;
+use robin_cli_core::processing::ImageOptions;
+use robin_cli_core::sources::{DownloadOptions, Novel, Serie};
 use robin_cli_core::utils::create_progress_bar;
-use zip::{write::FileOptions, CompressionMethod, ZipWriter};
 
-use epub_builder::{EpubBuilder, EpubContent, EpubVersion, ZipLibrary};
+mod archive;
+use archive::{
+    archive_chapters, archive_to, archive_volumes, collect_entries, keep_skipped, ArchiveFormat,
+    ArchiveOptions,
+};
 
 mod args;
-use args::{App, Commands, NovelFormat};
+use args::{App, ArchivePer, Commands, GroupBy, NovelFormat};
+
+mod epub;
+use epub::write_epub;
+
+mod single;
+use single::{manga_chapter_command, novel_chapter_command};
 
 mod utils;
-use utils::{copy_dir_all, find_in_info};
+use utils::find_in_info;
+
+mod batch;
+use batch::batch_command;
+
+mod get;
+use get::get_command;
+
+mod config;
+use config::{apply_config, explicit_ids, Config};
+
+mod chapters;
+use chapters::{chapters_command, ListFormat};
+
+mod info;
+use info::info_command;
+
+mod verify;
+use verify::verify_command;
+
+mod cookies;
+use cookies::cookies_command;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let app = App::parse();
+    let matches = App::command().get_matches();
+    let mut app = App::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    app.settings = Config::load(app.config.as_deref())?;
+    app.explicit = explicit_ids(&matches);
+    apply_config(&mut app, matches.subcommand().map(|(_, m)| m))?;
+
+    if let Commands::Cookies { action } = &app.command {
+        return cookies_command(&app, action);
+    }
+
+    let jar_path = app.jar_path();
+    let loaded = jar_path.as_deref().map(load_jar).transpose()?;
+    if let Some(loaded) = &loaded {
+        app.cookies = Some(Arc::new(CookieStoreMutex::new(loaded.clone())));
+    }
+
+    let result = match &app.command {
+        Commands::Batch { file, jobs } => batch_command(&app, file, *jobs).await,
+        Commands::Get {
+            url,
+            format,
+            options,
+        } => get_command(&app, url, format.as_deref(), options).await,
+        _ => run(&app).await,
+    };
+
+    // Keeps what the sites set during the run, like a renewed clearance
+    if let (Some(jar), Some(loaded), Some(path)) = (&app.cookies, &loaded, &jar_path) {
+        merge_jar(&jar.lock().unwrap(), loaded, path)?;
+    }
 
+    result
+}
+
+/// Runs one command, `batch` and `get` end up here with a `manga` or `novel` command.
+async fn run(app: &App) -> anyhow::Result<()> {
     match &app.command {
         Commands::Manga {
-            compress,
+            archive,
+            archive_per,
+            group_by,
             url,
+            no_series,
             filter,
+            since,
+            until,
+            sort,
+            convert_to,
+            max_width,
+            max_height,
+            grayscale,
+            jpeg_quality,
+            stitch,
+            page_height,
+            drop_credits,
+            credit_min_chapters,
+            credit_blocklist,
+            move_credits,
+            page_retries,
+            image_compression,
+            text_compression,
+            compression_level,
+            archive_threads,
         } => {
+            let image_options = ImageOptions {
+                convert_to: convert_to.map(Into::into),
+                max_width: *max_width,
+                max_height: *max_height,
+                grayscale: *grayscale,
+                jpeg_quality: *jpeg_quality,
+            };
+
+            let download_options = DownloadOptions {
+                page_retries: *page_retries,
+                image: (!image_options.is_noop()).then_some(image_options),
+                stitch: stitch.map(|s| s.to_mode(*page_height)),
+                templates: app.templates(),
+                temp_dir: app.temp_dir.clone(),
+                rate_limit: app.page_limit(),
+            };
+
+            if *no_series {
+                return manga_chapter_command(app, url, &download_options).await;
+            }
+
             let url = url;
-            let mut source = match_manga(url.clone(), app.proxy.clone()).await?;
+            let mut source = match_manga(url.clone(), app.net()).await?;
             source.find_chapters().await;
-            source.filter_chapters(filter.clone())?;
+
+            let report = source.check_chapters();
+            if !report.is_clean() {
+                println!("Warning, the chapter list looks off:\n{}", report);
+            }
+
+            source.filter_chapters(filter);
+            source.filter_by_date(*since, *until);
+            source.sort_chapters((*sort).into());
 
             let info = source.info().clone();
             let manga_name = info
                 .iter()
                 .find(|inf| inf.0 == "title")
-                .map(|uwu| uwu.1.clone())
+                .map(|uwu| {
+                    sanitize(&app.series_template.render(&NameContext {
+                        title: uwu.1.clone(),
+                        source: source.source_name().to_string(),
+                        lang: source.lang().to_string(),
+                        date: today(),
+                        ..Default::default()
+                    }))
+                })
                 .unwrap();
 
             println!(
@@ -43,72 +175,166 @@ async fn main() -> anyhow::Result<()> {
                 source.format_info(&info)
             );
 
-            let temp = source.download(app.concurrent_chapters).await?;
-
-            let mut pbar = create_progress_bar(source.chapter_count() as u64, "Adding files: ");
-
-            match compress {
-                true => {
-                    let output_folder = PathBuf::from(&app.output_folder);
-                    let destination = output_folder.join(format!("{}.zip", manga_name));
-                    println!("Destination set to: {}", destination.display());
-                    let f = File::create(destination)?;
-
-                    let mut zipper = ZipWriter::new(f);
-                    let zip_options = FileOptions::default()
-                        .compression_method(CompressionMethod::Bzip2)
-                        .compression_level(Some(9))
-                        .large_file(true);
-
-                    for ent in walkdir::WalkDir::new(&temp.path())
-                        .into_iter()
-                        .filter_map(|e| e.ok())
-                        .filter(|e| e.file_type().is_file())
-                    {
-                        let mut entry_file = File::open(&ent.path())?;
-                        let entry_file_name = ent.path().strip_prefix(&temp.path())?;
-
-                        zipper.start_file(entry_file_name.to_str().unwrap(), zip_options)?;
+            let output_folder = PathBuf::from(&app.output_folder);
+
+            // Archives are built from a work folder that is removed afterwards
+            let target = match archive {
+                Some(_) => app
+                    .temp_dir
+                    .clone()
+                    .unwrap_or(output_folder.clone())
+                    .join(format!(".{}.robin", manga_name)),
+                None => output_folder.join(&manga_name),
+            };
+
+            // A work folder left by a crashed run may hold chapters of another selection
+            if archive.is_some() && target.exists() {
+                remove_dir_all(&target)?;
+            }
 
-                        let mut buffer: Vec<u8> = Vec::new();
-                        entry_file.read_to_end(&mut buffer)?;
+            source
+                .download(app.concurrent_chapters, &target, &download_options)
+                .await?;
+
+            if *drop_credits || credit_blocklist.is_some() {
+                let credit_options = CreditOptions {
+                    min_chapters: if *drop_credits {
+                        *credit_min_chapters
+                    } else {
+                        0
+                    },
+                    blocklist: match credit_blocklist {
+                        Some(dir) => load_blocklist(dir)?,
+                        None => Vec::new(),
+                    },
+                    action: if *move_credits {
+                        CreditAction::MoveAside
+                    } else {
+                        CreditAction::Exclude
+                    },
+                    ..Default::default()
+                };
+
+                let skipped = drop_credits_in_serie(&target, &credit_options)?;
+
+                println!("Skipped {} credit/ad pages:", skipped.len());
+                for page in &skipped {
+                    println!("  {} ({})", page.path.display(), page.reason);
+                }
+            }
 
-                        zipper.write_all(&buffer)?;
+            match archive {
+                Some(format) => {
+                    let format: ArchiveFormat = (*format).into();
+                    create_dir_all(&output_folder)?;
 
-                        pbar.inc();
-                    }
+                    let archive_options = ArchiveOptions {
+                        image_method: (*image_compression).into(),
+                        text_method: (*text_compression).into(),
+                        level: *compression_level,
+                        threads: *archive_threads,
+                    };
 
-                    pbar.message("Compressing files...");
+                    let file_count = collect_entries(&target)?.len();
+                    let mut pbar = create_progress_bar(file_count as u64, "Adding files: ");
+
+                    let destination = match (group_by, archive_per) {
+                        (Some(GroupBy::Volume), _) => {
+                            let groups = Manifest::load(&target)?.volume_groups();
+                            archive_volumes(
+                                &target,
+                                &output_folder,
+                                &manga_name,
+                                &groups,
+                                format,
+                                &archive_options,
+                                || {
+                                    pbar.inc();
+                                },
+                            )?;
+                            output_folder.clone()
+                        }
+                        (None, ArchivePer::Series) => {
+                            let destination = output_folder.join(format!(
+                                "{}.{}",
+                                manga_name,
+                                format.extension()
+                            ));
+                            archive_to(&target, &destination, format, &archive_options, || {
+                                pbar.inc();
+                            })?;
+                            destination
+                        }
+                        (None, ArchivePer::Chapter) => {
+                            let destination = output_folder.join(&manga_name);
+                            archive_chapters(
+                                &target,
+                                &destination,
+                                format,
+                                &archive_options,
+                                || {
+                                    pbar.inc();
+                                },
+                            )?;
+                            destination
+                        }
+                    };
 
-                    zipper.finish()?;
+                    let kept = keep_skipped(&target, &output_folder, &manga_name)?;
+                    remove_dir_all(&target)?;
 
                     pbar.finish_print("Compressed!");
+                    println!("Archived to: {}", destination.display());
+                    if let Some(kept) = kept {
+                        println!("Skipped pages moved to: {}", kept.display());
+                    }
                 }
-                false => {
-                    let output_folder = PathBuf::from(&app.output_folder);
-                    let destination = output_folder.join(manga_name);
-
-                    println!("Copying files to: {}", destination.display());
-
-                    create_dir_all(&destination)?;
-                    copy_dir_all(&temp, destination)?;
+                None => {
+                    println!("Downloaded to: {}", target.display());
                 }
             }
         }
         Commands::Novel {
             url,
+            no_series,
             filter,
             format,
+            group_by,
         } => {
-            let mut source = match_novel(url.clone(), app.proxy.clone()).await?;
+            if group_by.is_some() && matches!(format, NovelFormat::Txt) {
+                bail!("--group-by needs --format epub");
+            }
+
+            if *no_series {
+                if matches!(format, NovelFormat::Epub) {
+                    bail!("--no-series only writes text files");
+                }
+                return novel_chapter_command(app, url).await;
+            }
+
+            let mut source = match_novel(url.clone(), app.net()).await?;
             source.find_chapters().await;
-            source.filter_chapters(filter.clone())?;
+
+            let report = source.check_chapters();
+            if !report.is_clean() {
+                println!("Warning, the chapter list looks off:\n{}", report);
+            }
+
+            source.filter_chapters(filter);
 
             let info = source.info().clone();
             let novel_name = info
                 .iter()
                 .find(|inf| inf.0 == "title")
-                .map(|uwu| uwu.1.clone())
+                .map(|uwu| {
+                    sanitize(&app.series_template.render(&NameContext {
+                        title: uwu.1.clone(),
+                        source: source.source_name().to_string(),
+                        lang: source.lang().to_string(),
+                        date: today(),
+                        ..Default::default()
+                    }))
+                })
                 .unwrap();
 
             println!(
@@ -122,101 +348,135 @@ async fn main() -> anyhow::Result<()> {
                 .map(|(a, b)| (a.to_string(), b.to_string()))
                 .collect::<Vec<(String, String)>>();
 
-            let temp = source.download(app.concurrent_chapters).await?;
+            let download_options = DownloadOptions {
+                templates: app.templates(),
+                temp_dir: app.temp_dir.clone(),
+                rate_limit: app.page_limit(),
+                ..Default::default()
+            };
+
+            let output_folder = PathBuf::from(&app.output_folder);
+
+            // Ebooks are built from a work folder that is removed afterwards
+            let target = match format {
+                NovelFormat::Txt => output_folder.join(&novel_name),
+                NovelFormat::Epub => app
+                    .temp_dir
+                    .clone()
+                    .unwrap_or(output_folder.clone())
+                    .join(format!(".{}.robin", novel_name)),
+            };
+
+            // Same as for manga, chapters of a crashed run would end up in the ebook
+            if matches!(format, NovelFormat::Epub) && target.exists() {
+                remove_dir_all(&target)?;
+            }
+
+            source
+                .download(app.concurrent_chapters, &target, &download_options)
+                .await?;
 
             match format {
                 NovelFormat::Txt => {
-                    let output_folder = PathBuf::from(&app.output_folder);
-                    let destination = output_folder.join(novel_name);
-
-                    println!("Copying files to: {}", destination.display());
-
-                    create_dir_all(&destination)?;
-                    copy_dir_all(&temp, destination)?;
+                    println!("Downloaded to: {}", target.display());
                 }
                 NovelFormat::Epub => {
-                    let mut pbar =
-                        create_progress_bar(source.chapters().len() as u64, "Adding files: ");
-
-                    // get all full file paths in the temp directory as &str
-                    let mut files = walkdir::WalkDir::new(&temp.path())
-                        .into_iter()
-                        .filter_map(|e| e.ok())
-                        .filter(|e| e.file_type().is_file())
-                        .map(|e| e.path().to_str().unwrap().to_string())
-                        .collect::<Vec<String>>();
-
-                    // find the cover image
-                    let cover = files.iter().find(|f| f.contains("cover")).unwrap().clone();
-
-                    // open cover image and convert to bytes
-                    let mut cover_file = File::open(&cover)?;
-                    let mut cover_bytes = Vec::new();
-                    cover_file.read_to_end(&mut cover_bytes)?;
-
-                    // get the cover image extension and turn into a mimetype
-                    let cover_ext = cover.split('.').last().unwrap();
-                    let cover_mimetype = match cover_ext {
-                        "jpg" | "jpeg" => "image/jpeg",
-                        "png" => "image/png",
-                        "gif" => "image/gif",
-                        _ => "image/jpeg",
+                    let manifest = Manifest::load(&target)?;
+                    let title = find_in_info(&info, "title").unwrap();
+                    let author = find_in_info(&info, "author").unwrap();
+
+                    let cover = manifest
+                        .files
+                        .iter()
+                        .find(|e| e.path.starts_with("cover."))
+                        .map(|e| target.join(&e.path))
+                        .ok_or_else(|| anyhow!("no cover in {}", target.display()))?;
+
+                    // One book for everything, or one per volume
+                    let books = match group_by {
+                        Some(GroupBy::Volume) => manifest
+                            .volume_groups()
+                            .into_iter()
+                            .map(|(volume, files)| {
+                                let label = volume_label(volume);
+                                (
+                                    format!("{} {}", novel_name, label),
+                                    format!("{} {}", title, label),
+                                    files,
+                                )
+                            })
+                            .collect::<Vec<_>>(),
+                        None => vec![(
+                            novel_name.clone(),
+                            title.clone(),
+                            manifest
+                                .files
+                                .iter()
+                                .filter(|e| e.chapter_url.is_some())
+                                .map(|e| e.path.clone())
+                                .collect(),
+                        )],
                     };
 
-                    // remove cover from file list
-                    files.retain(|f| !f.contains("cover"));
-                    files.sort_by(|a, b| natord::compare(&a, &b));
-
-                    let mut book_builder = EpubBuilder::new(ZipLibrary::new().unwrap()).unwrap();
-
-                    book_builder
-                        .epub_version(EpubVersion::V30)
-                        .metadata("title", find_in_info(&info, "title").unwrap())
-                        .unwrap()
-                        .metadata("author", find_in_info(&info, "author").unwrap())
-                        .unwrap()
-                        .add_cover_image(cover, cover_bytes.as_slice(), cover_mimetype)
-                        .unwrap();
-
-                    for f_name in files.into_iter() {
-                        let mut f = File::open(&f_name)?;
-                        let mut contents = String::new();
-                        f.read_to_string(&mut contents)?;
-
-                        let ch_name = f_name.rsplit_once(".").unwrap().0;
-                        let chapter_title = format!("{}.xhtml", ch_name);
-
-                        let paragraphs = contents
-                            .split("\n")
-                            .map(|p| format!("<p>{}</p>", p))
-                            .collect::<Vec<String>>()
-                            .join("\n");
-
-                        let epub_content = EpubContent::new(chapter_title, paragraphs.as_bytes())
-                            .title(ch_name)
-                            .reftype(epub_builder::ReferenceType::Text);
-
-                        book_builder.add_content(epub_content).unwrap();
-
-                        pbar.inc();
-                    }
-
-                    book_builder.inline_toc();
-
-                    pbar.finish_println("Epub building...");
+                    let mut pbar =
+                        create_progress_bar(source.chapters().len() as u64, "Adding files: ");
 
-                    let output_folder = PathBuf::from(&app.output_folder);
                     create_dir_all(&output_folder)?;
+                    let mut output_files = Vec::new();
+
+                    for (name, title, mut files) in books {
+                        files.sort_by(|a, b| natord::compare(a, b));
+                        let chapters = files
+                            .iter()
+                            .map(|f| {
+                                let released = manifest
+                                    .files
+                                    .iter()
+                                    .find(|e| e.path == *f)
+                                    .and_then(|e| e.released);
+                                (target.join(f), released)
+                            })
+                            .collect::<Vec<_>>();
+
+                        let output_file = output_folder.join(format!("{}.epub", name));
+                        write_epub(&title, &author, &cover, &chapters, &output_file, || {
+                            pbar.inc();
+                        })?;
+                        output_files.push(output_file);
+                    }
 
-                    let output_file = output_folder.join(format!("{}.epub", novel_name));
-                    let mut f = File::create(&output_file)?;
-
-                    book_builder.generate(&mut f).unwrap();
+                    pbar.finish_print("Epub built!");
+                    remove_dir_all(&target)?;
 
-                    println!("Epub created at: {}", output_file.display());
+                    for output_file in output_files {
+                        println!("Epub created at: {}", output_file.display());
+                    }
                 }
             }
         }
+        Commands::Info { url, json } => {
+            info_command(app, url, *json).await?;
+        }
+        Commands::Chapters {
+            url,
+            filter,
+            json,
+            csv,
+            check,
+        } => {
+            let format = match (json, csv) {
+                (true, _) => ListFormat::Json,
+                (_, true) => ListFormat::Csv,
+                _ => ListFormat::Table,
+            };
+            chapters_command(app, url, filter, format, *check).await?;
+        }
+        Commands::Verify { path, repair } => {
+            verify_command(app, path, *repair).await?;
+        }
+        Commands::Batch { .. } | Commands::Get { .. } | Commands::Cookies { .. } => {
+            bail!("batch entries are always manga or novel commands");
+        }
     }
 
     Ok(())
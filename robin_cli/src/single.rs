@@ -0,0 +1,70 @@
+use std::fs::{create_dir_all, write};
+use std::path::PathBuf;
+
+use anyhow::bail;
+use robin_cli_core::matcher::{match_manga_chapter, match_novel_chapter};
+use robin_cli_core::naming::{today, NameContext};
+use robin_cli_core::path_safety::sanitize;
+use robin_cli_core::sources::{Chapter, DownloadOptions, NovelChapter};
+
+use crate::args::App;
+use crate::config::host;
+
+/// `robin manga --no-series`: the pages of one chapter, in a folder named after it
+/// (chapter template). Pages are stitched, named and processed like in a serie.
+pub async fn manga_chapter_command(
+    app: &App,
+    url: &str,
+    opts: &DownloadOptions,
+) -> anyhow::Result<()> {
+    let (client, mut chapter) = match_manga_chapter(url.to_string(), app.net()).await?;
+
+    // No serie here, so no {title} either
+    let ctx = NameContext {
+        source: host(url).to_string(),
+        volume: chapter.volume(),
+        chapter: chapter.number().chapter,
+        chapter_title: chapter.title().to_string(),
+        date: today(),
+        ..Default::default()
+    };
+
+    let target =
+        PathBuf::from(&app.output_folder).join(sanitize(&opts.templates.chapter.render(&ctx)));
+    if target.exists() {
+        bail!("{} already exists", target.display());
+    }
+    let raw = target.join(".pages");
+    create_dir_all(&raw)?;
+
+    println!("Downloading {}", chapter.title().trim());
+    chapter.download(&client, &raw, opts).await?;
+    chapter.finish_pages(&raw, &target, &ctx, opts).await?;
+
+    if !chapter.is_complete() {
+        println!(
+            "Some pages failed:\n{}",
+            chapter.format_info(&chapter.info())
+        );
+    }
+    println!("Downloaded to: {}", target.display());
+
+    Ok(())
+}
+
+/// `robin novel --no-series`: one chapter as a text file.
+pub async fn novel_chapter_command(app: &App, url: &str) -> anyhow::Result<()> {
+    let (client, mut chapter) = match_novel_chapter(url.to_string(), app.net()).await?;
+
+    let output_folder = PathBuf::from(&app.output_folder);
+    create_dir_all(&output_folder)?;
+
+    println!("Downloading {}", chapter.title().trim());
+    chapter.download(&client).await?;
+
+    let target = output_folder.join(format!("{}.txt", sanitize(chapter.title())));
+    write(&target, chapter.content())?;
+    println!("Downloaded to: {}", target.display());
+
+    Ok(())
+}
@@ -1,23 +1,5 @@
-use std::fs;
-use std::path::Path;
-
-// Copied from https://stackoverflow.com/a/65192210
-pub fn copy_dir_all(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> anyhow::Result<()> {
-    fs::create_dir_all(&dst)?;
-    for entry in fs::read_dir(src)? {
-        let entry = entry?;
-        let ty = entry.file_type()?;
-        if ty.is_dir() {
-            copy_dir_all(entry.path(), dst.as_ref().join(entry.file_name()))?;
-        } else {
-            fs::copy(entry.path(), dst.as_ref().join(entry.file_name()))?;
-        }
-    }
-    Ok(())
-}
-
 pub fn find_in_info(info: &Vec<(String, String)>, target: &str) -> Option<String> {
     info.iter()
         .find(|(key, _)| key == &target)
         .map(|(_, value)| value.clone())
-}
\ No newline at end of file
+}
@@ -0,0 +1,261 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use anyhow::{anyhow, bail};
+use robin_cli_core::credits::drop_credits_in_serie;
+use robin_cli_core::manifest::{
+    compare, hash_reader, scan_dir, FoundFiles, Manifest, VerifyReport, MANIFEST_FILE,
+};
+use robin_cli_core::matcher::{match_manga, match_novel};
+use robin_cli_core::sources::{Chapter, DownloadOptions, Novel, NovelChapter, Serie};
+
+use crate::archive::{read_archive, ArchiveFormat};
+use crate::args::App;
+
+/// Hashes the files of an archive, as paths under `prefix`, and reads its manifest.
+fn scan_archive(path: &Path, prefix: &str) -> anyhow::Result<(Option<Manifest>, FoundFiles)> {
+    let mut manifest = None;
+    let mut found = FoundFiles::new();
+
+    read_archive(path, |name, reader| {
+        if name == MANIFEST_FILE {
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data)?;
+            manifest = Some(serde_json::from_slice(&data)?);
+        } else {
+            found.insert(format!("{}{}", prefix, name), hash_reader(reader).ok());
+        }
+        Ok(())
+    })?;
+
+    Ok((manifest, found))
+}
+
+/// Checks a serie folder or archive against its manifest. Chapters archived on their
+/// own (`--archive-per chapter`) are checked inside their archives.
+/// Also tells if anything was archived, those can't be repaired in place.
+fn check(path: &Path) -> anyhow::Result<(Manifest, VerifyReport, bool)> {
+    if path.is_file() {
+        let (manifest, found) = scan_archive(path, "")?;
+        let manifest =
+            manifest.ok_or_else(|| anyhow!("{} has no {}", path.display(), MANIFEST_FILE))?;
+        let report = compare(&manifest, &found);
+        return Ok((manifest, report, true));
+    }
+
+    let manifest = Manifest::load(path)?;
+    let mut found = scan_dir(path, &manifest)?;
+    let mut archived = false;
+
+    let archives = found
+        .keys()
+        .filter(|name| !name.contains('/'))
+        .filter_map(|name| {
+            let format = ArchiveFormat::from_name(name)?;
+            let stem = name[..name.len() - format.extension().len() - 1].to_string();
+            let prefix = format!("{}/", stem);
+            manifest
+                .files
+                .iter()
+                .any(|e| e.path.starts_with(&prefix))
+                .then_some((name.clone(), prefix))
+        })
+        .collect::<Vec<(String, String)>>();
+
+    for (name, prefix) in archives {
+        found.remove(&name);
+        found.extend(scan_archive(&path.join(&name), &prefix)?.1);
+        archived = true;
+    }
+
+    let report = compare(&manifest, &found);
+    Ok((manifest, report, archived))
+}
+
+fn print_report(report: &VerifyReport) {
+    println!("Checked {} files.", report.checked);
+
+    for (label, paths) in [
+        ("Missing", &report.missing),
+        ("Corrupted", &report.corrupted),
+        ("Extra", &report.extra),
+    ] {
+        if !paths.is_empty() {
+            println!("\n{} ({}):", label, paths.len());
+            for path in paths {
+                println!("  {}", path);
+            }
+        }
+    }
+}
+
+/// `robin verify`: re-hashes a serie folder or archive and optionally downloads broken
+/// chapters again.
+pub async fn verify_command(app: &App, path: &Path, repair: bool) -> anyhow::Result<()> {
+    let (manifest, report, archived) = check(path)?;
+    print_report(&report);
+
+    if report.is_ok() {
+        println!("\nEverything is fine!");
+        return Ok(());
+    }
+
+    if !repair {
+        bail!(
+            "{} missing and {} corrupted files, run with --repair to download them again",
+            report.missing.len(),
+            report.corrupted.len()
+        );
+    }
+
+    if archived {
+        bail!("archives can't be repaired in place, download the serie again");
+    }
+
+    let chapter_urls = report
+        .broken(&manifest)
+        .iter()
+        .filter_map(|e| e.chapter_url.clone())
+        .collect::<HashSet<String>>();
+
+    println!("\nDownloading {} chapters again...", chapter_urls.len());
+
+    let download_options = DownloadOptions {
+        temp_dir: app.temp_dir.clone(),
+        rate_limit: app.page_limit(),
+        ..manifest.download_options()?
+    };
+
+    // Cover and details are always written again, so files without a chapter are fixed too
+    match manifest.kind.as_str() {
+        "novel" => {
+            let mut source = match_novel(manifest.source.clone(), app.net()).await?;
+            source.find_chapters().await;
+            source.chapters().retain(|c| chapter_urls.contains(c.url()));
+            source
+                .download(app.concurrent_chapters, path, &download_options)
+                .await?;
+        }
+        _ => {
+            let mut source = match_manga(manifest.source.clone(), app.net()).await?;
+            source.find_chapters().await;
+            source.chapters().retain(|c| chapter_urls.contains(c.url()));
+            source
+                .download(app.concurrent_chapters, path, &download_options)
+                .await?;
+        }
+    }
+
+    if let Some(credits) = &manifest.credits {
+        drop_credits_in_serie(path, credits)?;
+    }
+
+    let (_, report, _) = check(path)?;
+    println!();
+    print_report(&report);
+
+    if !report.is_ok() {
+        bail!("some files are still broken");
+    }
+
+    println!("\nRepaired!");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod verify_tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use robin_cli_core::manifest::ManifestEntry;
+
+    use super::*;
+    use crate::archive::{archive_chapters, archive_to, archive_volumes, ArchiveOptions};
+
+    fn sample_serie(root: &Path) {
+        let mut manifest =
+            Manifest::new("manga", "https://example.com", &DownloadOptions::default());
+
+        for (ch, volume) in [("Chapter 1", 1.0), ("Chapter 2", 2.0)] {
+            for page in ["0000.jpg", "0001.jpg"] {
+                let path = format!("{}/{}", ch, page);
+                let bytes = format!("{} {}", ch, page).into_bytes();
+                fs::create_dir_all(root.join(ch)).unwrap();
+                fs::write(root.join(&path), &bytes).unwrap();
+
+                manifest.insert([ManifestEntry {
+                    volume: Some(volume),
+                    chapter_url: Some(format!("https://example.com/{}", ch)),
+                    ..ManifestEntry::from_bytes(path, &bytes, "https://example.com/p".into())
+                }]);
+            }
+        }
+
+        manifest.save(root).unwrap();
+    }
+
+    #[test]
+    fn test_archives_are_checked() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join(".uwu.robin");
+        let out = dir.path().join("out");
+        sample_serie(&root);
+        fs::create_dir_all(&out).unwrap();
+
+        for format in [
+            ArchiveFormat::Cbz,
+            ArchiveFormat::TarZst,
+            ArchiveFormat::SevenZ,
+        ] {
+            let archive = out.join(format!("uwu.{}", format.extension()));
+            archive_to(&root, &archive, format, &ArchiveOptions::default(), || {}).unwrap();
+
+            let (_, report, archived) = check(&archive).unwrap();
+            assert!(report.is_ok() && report.extra.is_empty(), "{:?}", format);
+            assert_eq!(report.checked, 4);
+            assert!(archived);
+        }
+
+        let volumes = archive_volumes(
+            &root,
+            &out,
+            "uwu",
+            &Manifest::load(&root).unwrap().volume_groups(),
+            ArchiveFormat::Zip,
+            &ArchiveOptions::default(),
+            || {},
+        )
+        .unwrap();
+        for volume in volumes {
+            let (_, report, _) = check(&volume).unwrap();
+            assert!(report.is_ok() && report.extra.is_empty());
+            // Two pages and the ComicInfo of the volume
+            assert_eq!(report.checked, 3);
+        }
+
+        let per_chapter = out.join("uwu");
+        let options = ArchiveOptions::default();
+        archive_chapters(&root, &per_chapter, ArchiveFormat::Cbz, &options, || {}).unwrap();
+        let (_, report, archived) = check(&per_chapter).unwrap();
+        assert!(report.is_ok() && report.extra.is_empty());
+        assert!(archived);
+    }
+
+    #[test]
+    fn test_broken_archive() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join(".uwu.robin");
+        sample_serie(&root);
+        fs::write(root.join("Chapter 1/0000.jpg"), b"changed").unwrap();
+        fs::remove_file(root.join("Chapter 2/0001.jpg")).unwrap();
+
+        let archive: PathBuf = dir.path().join("uwu.cbz");
+        let options = ArchiveOptions::default();
+        archive_to(&root, &archive, ArchiveFormat::Cbz, &options, || {}).unwrap();
+
+        let (_, report, _) = check(&archive).unwrap();
+        assert_eq!(report.corrupted, ["Chapter 1/0000.jpg"]);
+        assert_eq!(report.missing, ["Chapter 2/0001.jpg"]);
+    }
+}
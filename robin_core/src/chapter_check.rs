@@ -0,0 +1,188 @@
+use std::cmp::Ordering;
+
+use crate::chapter_number::ChapterNumber;
+
+// Chapter number and part
+type NumberKey = (f64, Option<u32>);
+
+/// Problems in a chapter list found by `check_chapters`.
+#[derive(Debug, Default, PartialEq)]
+pub struct ChapterReport {
+    /// Whole chapter numbers missing between the first and last chapter, as ranges
+    pub missing: Vec<(i64, i64)>,
+    /// Chapters sharing a number (and part), re-uploads or several scanlation groups
+    pub duplicates: Vec<(f64, Vec<String>)>,
+    /// Chapters listed against the order of the rest of the list
+    pub out_of_order: Vec<String>,
+    /// Chapters without a number that are not prologues, extras...
+    pub unparsed: Vec<String>,
+}
+
+impl ChapterReport {
+    pub fn is_clean(&self) -> bool {
+        self.missing.is_empty()
+            && self.duplicates.is_empty()
+            && self.out_of_order.is_empty()
+            && self.unparsed.is_empty()
+    }
+}
+
+fn quoted(names: &[String]) -> String {
+    names
+        .iter()
+        .map(|n| format!("\"{}\"", n.trim()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl std::fmt::Display for ChapterReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.missing.is_empty() {
+            let ranges = self
+                .missing
+                .iter()
+                .map(|(from, to)| match from == to {
+                    true => from.to_string(),
+                    false => format!("{}-{}", from, to),
+                })
+                .collect::<Vec<_>>();
+            writeln!(f, "Missing chapters: {}", ranges.join(", "))?;
+        }
+        if !self.duplicates.is_empty() {
+            writeln!(f, "Duplicate chapters:")?;
+            for (number, names) in &self.duplicates {
+                writeln!(f, "  {}: {}", number, quoted(names))?;
+            }
+        }
+        if !self.out_of_order.is_empty() {
+            writeln!(f, "Listed out of order: {}", quoted(&self.out_of_order))?;
+        }
+        if !self.unparsed.is_empty() {
+            writeln!(f, "No chapter number: {}", quoted(&self.unparsed))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Looks for gaps, duplicates and ordering problems in a chapter list, as listed by the source.
+pub fn check_chapters(chapters: &[(String, ChapterNumber)]) -> ChapterReport {
+    let mut report = ChapterReport::default();
+
+    let numbered = chapters
+        .iter()
+        .filter_map(|(name, n)| n.value().map(|value| (name, value, n.part)))
+        .collect::<Vec<_>>();
+
+    report.unparsed = chapters
+        .iter()
+        .filter(|(_, n)| n.value().is_none() && n.special.is_none())
+        .map(|(name, _)| name.clone())
+        .collect();
+
+    let mut wholes = numbered
+        .iter()
+        .map(|(_, value, _)| value.floor() as i64)
+        .collect::<Vec<i64>>();
+    wholes.sort();
+    wholes.dedup();
+
+    for pair in wholes.windows(2) {
+        if pair[1] - pair[0] > 1 {
+            report.missing.push((pair[0] + 1, pair[1] - 1));
+        }
+    }
+
+    let mut groups: Vec<(NumberKey, Vec<String>)> = Vec::new();
+    for (name, value, part) in &numbered {
+        match groups.iter_mut().find(|(key, _)| *key == (*value, *part)) {
+            Some((_, names)) => names.push(name.to_string()),
+            None => groups.push(((*value, *part), vec![name.to_string()])),
+        }
+    }
+    groups.sort_by(|(a, _), (b, _)| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+    report.duplicates = groups
+        .into_iter()
+        .filter(|(_, names)| names.len() > 1)
+        .map(|((value, _), names)| (value, names))
+        .collect();
+
+    // Sources list oldest or newest first, whichever most of the list does is right
+    let cmp = |a: &(&String, f64, Option<u32>), b: &(&String, f64, Option<u32>)| {
+        a.1.total_cmp(&b.1).then(a.2.cmp(&b.2))
+    };
+    let ascending = numbered
+        .windows(2)
+        .filter(|p| cmp(&p[0], &p[1]) == Ordering::Less)
+        .count();
+    let descending = numbered
+        .windows(2)
+        .filter(|p| cmp(&p[0], &p[1]) == Ordering::Greater)
+        .count();
+    let wrong = match ascending >= descending {
+        true => Ordering::Greater,
+        false => Ordering::Less,
+    };
+
+    report.out_of_order = numbered
+        .windows(2)
+        .filter(|p| cmp(&p[0], &p[1]) == wrong)
+        .map(|p| p[1].0.clone())
+        .collect();
+
+    report
+}
+
+#[cfg(test)]
+mod chapter_check_tests {
+    use super::*;
+
+    fn check(names: &[&str]) -> ChapterReport {
+        let chapters = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                (
+                    name.to_string(),
+                    ChapterNumber::parse(name, names.len() - i),
+                )
+            })
+            .collect::<Vec<_>>();
+        check_chapters(&chapters)
+    }
+
+    #[test]
+    fn test_clean_list() {
+        let report = check(&["Bölüm 3", "Bölüm 2.5", "Bölüm 2", "Bölüm 1", "Prologue"]);
+        assert!(report.is_clean());
+        assert_eq!(report.to_string(), "");
+    }
+
+    #[test]
+    fn test_problems() {
+        let report = check(&[
+            "Bölüm 50",
+            "Bölüm 44",
+            "Bölüm 40",
+            "Bölüm 40 (re-upload)",
+            "Bölüm 42",
+            "Bölüm 39",
+            "Duyuru",
+        ]);
+
+        assert_eq!(report.missing, [(41, 41), (43, 43), (45, 49)]);
+        assert_eq!(
+            report.duplicates,
+            [(40.0, vec!["Bölüm 40".into(), "Bölüm 40 (re-upload)".into()])]
+        );
+        assert_eq!(report.out_of_order, ["Bölüm 42"]);
+        assert_eq!(report.unparsed, ["Duyuru"]);
+        assert_eq!(
+            report.to_string(),
+            "Missing chapters: 41, 43, 45-49\n\
+             Duplicate chapters:\n  40: \"Bölüm 40\", \"Bölüm 40 (re-upload)\"\n\
+             Listed out of order: \"Bölüm 42\"\n\
+             No chapter number: \"Duyuru\"\n"
+        );
+    }
+}
@@ -0,0 +1,286 @@
+use std::cmp::Ordering;
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+/// Chapters that are not part of the main numbering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Special {
+    Prologue,
+    Epilogue,
+    Extra,
+    SideStory,
+}
+
+impl Special {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Special::Prologue => "Prologue",
+            Special::Epilogue => "Epilogue",
+            Special::Extra => "Extra",
+            Special::SideStory => "Side Story",
+        }
+    }
+}
+
+/// Number of a chapter as parsed from its name by `ChapterNumber::parse`.
+///
+/// `Vol. 2 Ch. 10.5`, `Bölüm 12,5`, `10a`, `10 part 2`, `Prologue`... Chapters without a
+/// number still have `ordinal`, their position in reading order.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ChapterNumber {
+    pub volume: Option<f64>,
+    pub chapter: Option<f64>,
+    /// `10a` is part 1, `10 part 2` part 2
+    pub part: Option<u32>,
+    pub special: Option<Special>,
+    /// Position in the chapter list, oldest first, from 1
+    pub ordinal: usize,
+}
+
+struct Patterns {
+    volume: Regex,
+    chapter: Regex,
+    chapter_after: Regex,
+    part: Regex,
+    number: Regex,
+    specials: Vec<(Regex, Special)>,
+}
+
+fn patterns() -> &'static Patterns {
+    static PATTERNS: OnceLock<Patterns> = OnceLock::new();
+
+    PATTERNS.get_or_init(|| {
+        let re = |s: &str| Regex::new(s).unwrap();
+        // 12, 12.5 and 12,5
+        let num = r"(\d+(?:[.,]\d+)?)";
+
+        Patterns {
+            volume: re(&format!(r"(?i)\b(?:volume|vol|cilt|v)\.?\s*{num}")),
+            chapter: re(&format!(
+                r"(?i)\b(?:chapter|chap|ch|bölüm|episode|ep|c)\.?\s*{num}([a-z]\b)?"
+            )),
+            // Turkish "12. Bölüm"
+            chapter_after: re(&format!(r"(?i){num}\.?\s*bölüm")),
+            part: re(r"(?i)\b(?:part|pt|kısım)\.?\s*(\d+)"),
+            number: re(&format!(r"{num}([a-z]\b)?")),
+            specials: vec![
+                (re(r"(?i)\bprolog(?:ue)?\b|\bön\s?söz\b"), Special::Prologue),
+                (
+                    re(r"(?i)\bepilog(?:ue)?\b|\bson\s?söz\b"),
+                    Special::Epilogue,
+                ),
+                (
+                    re(r"(?i)\bside[\s-]?stor(?:y|ies)\b|\byan\s?hikaye"),
+                    Special::SideStory,
+                ),
+                (
+                    re(r"(?i)\bextra\b|\bekstra\b|\bbonus\b|\bspecial\b|\bözel\b"),
+                    Special::Extra,
+                ),
+            ],
+        }
+    })
+}
+
+fn parse_num(s: &str) -> Option<f64> {
+    let n = s.replace(',', ".").parse::<f64>().ok()?;
+
+    // Round to 2 decimal points. Example: 123.45
+    Some((n * 100.0).round() / 100.0)
+}
+
+// a -> 1, b -> 2...
+fn letter_part(s: &str) -> Option<u32> {
+    let c = s.chars().next()?.to_ascii_lowercase();
+    Some(c as u32 - 'a' as u32 + 1)
+}
+
+impl ChapterNumber {
+    /// Parses a chapter name. `ordinal` is the position of the chapter in reading order.
+    pub fn parse(name: &str, ordinal: usize) -> ChapterNumber {
+        let p = patterns();
+        let mut number = ChapterNumber {
+            ordinal,
+            special: p
+                .specials
+                .iter()
+                .find(|(re, _)| re.is_match(name))
+                .map(|(_, s)| *s),
+            ..Default::default()
+        };
+
+        // Taken out of the name, so the numbers left over are chapter numbers
+        let mut rest = name.to_string();
+
+        if let Some(caps) = p.volume.captures(&rest) {
+            number.volume = parse_num(&caps[1]);
+            rest.replace_range(caps.get(0).unwrap().range(), " ");
+        }
+        if let Some(caps) = p.part.captures(&rest) {
+            number.part = caps[1].parse().ok();
+            rest.replace_range(caps.get(0).unwrap().range(), " ");
+        }
+
+        let explicit = p
+            .chapter
+            .captures(&rest)
+            .or_else(|| p.chapter_after.captures(&rest));
+
+        let caps = match explicit {
+            Some(caps) => Some(caps),
+            // "Extra 2" is the second extra, not chapter 2
+            None if number.special.is_some() => None,
+            None => p.number.captures(&rest),
+        };
+
+        if let Some(caps) = caps {
+            number.chapter = parse_num(&caps[1]);
+            if let Some(letter) = caps.get(2) {
+                number.part = number.part.or(letter_part(letter.as_str()));
+            }
+        }
+
+        number
+    }
+
+    /// Number used by `--filter`, a prologue without a number counts as chapter 0.
+    pub fn value(&self) -> Option<f64> {
+        match (self.chapter, self.special) {
+            (Some(n), _) => Some(n),
+            (None, Some(Special::Prologue)) => Some(0.0),
+            _ => None,
+        }
+    }
+
+    /// Reading order: prologues, numbered chapters (then parts), then everything else as listed.
+    pub fn cmp_reading(&self, other: &ChapterNumber) -> Ordering {
+        let group = |n: &ChapterNumber| match (n.chapter, n.special) {
+            (None, Some(Special::Prologue)) => 0,
+            (Some(_), _) => 1,
+            _ => 2,
+        };
+
+        group(self)
+            .cmp(&group(other))
+            .then_with(|| {
+                self.chapter
+                    .unwrap_or_default()
+                    .total_cmp(&other.chapter.unwrap_or_default())
+            })
+            .then_with(|| self.part.cmp(&other.part))
+            .then_with(|| self.ordinal.cmp(&other.ordinal))
+    }
+}
+
+impl std::fmt::Display for ChapterNumber {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut words = Vec::new();
+
+        if let Some(volume) = self.volume {
+            words.push(format!("Vol. {}", volume));
+        }
+        match (self.chapter, self.special) {
+            (Some(chapter), _) => words.push(format!("Ch. {}", chapter)),
+            (None, Some(special)) => words.push(special.as_str().to_string()),
+            (None, None) => words.push(format!("#{}", self.ordinal)),
+        }
+        if let Some(part) = self.part {
+            words.push(format!("part {}", part));
+        }
+
+        write!(f, "{}", words.join(" "))
+    }
+}
+
+/// Volume part of output names, e.g. "Vol. 2".
+pub fn volume_label(volume: Option<f64>) -> String {
+    match volume {
+        Some(volume) => format!("Vol. {}", volume),
+        None => "No Volume".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod chapter_number_tests {
+    use super::*;
+
+    fn parse(name: &str) -> ChapterNumber {
+        ChapterNumber::parse(name, 7)
+    }
+
+    #[test]
+    fn test_numbers() {
+        assert_eq!(parse("Bölüm 45").chapter, Some(45.0));
+        assert_eq!(parse("12. Bölüm").chapter, Some(12.0));
+        assert_eq!(parse("Bölüm 12,5").chapter, Some(12.5));
+        assert_eq!(parse("Chapter 10.5 - The End?").chapter, Some(10.5));
+        assert_eq!(parse("Chapter 3 - 100 Swords").chapter, Some(3.0));
+        assert_eq!(parse("Omniscient Reader 150").chapter, Some(150.0));
+        assert_eq!(parse("Nothing here").chapter, None);
+    }
+
+    #[test]
+    fn test_volumes_and_parts() {
+        let n = parse("Volume 1 Chapter 23");
+        assert_eq!((n.volume, n.chapter), (Some(1.0), Some(23.0)));
+
+        let n = parse("Vol. 2 Ch. 10.5");
+        assert_eq!((n.volume, n.chapter), (Some(2.0), Some(10.5)));
+
+        let n = parse("Chapter 10a");
+        assert_eq!((n.chapter, n.part), (Some(10.0), Some(1)));
+
+        let n = parse("Chapter 10 Part 2");
+        assert_eq!((n.chapter, n.part), (Some(10.0), Some(2)));
+
+        let n = parse("10th Anniversary");
+        assert_eq!((n.chapter, n.part), (Some(10.0), None));
+    }
+
+    #[test]
+    fn test_specials() {
+        let n = parse("Prologue");
+        assert_eq!(
+            (n.special, n.chapter, n.value()),
+            (Some(Special::Prologue), None, Some(0.0))
+        );
+
+        let n = parse("Side Story 3");
+        assert_eq!((n.special, n.chapter), (Some(Special::SideStory), None));
+
+        let n = parse("Chapter 50.5 - Extra");
+        assert_eq!((n.special, n.chapter), (Some(Special::Extra), Some(50.5)));
+
+        assert_eq!(parse("Epilogue").value(), None);
+        assert_eq!(parse("Epilogue").ordinal, 7);
+    }
+
+    #[test]
+    fn test_reading_order() {
+        let mut chapters = [
+            "Epilogue",
+            "Chapter 2",
+            "Chapter 1b",
+            "Prologue",
+            "Chapter 1a",
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, name)| ChapterNumber::parse(name, i + 1))
+        .collect::<Vec<_>>();
+        chapters.sort_by(|a, b| a.cmp_reading(b));
+
+        let shown = chapters.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            shown,
+            [
+                "Prologue",
+                "Ch. 1 part 1",
+                "Ch. 1 part 2",
+                "Ch. 2",
+                "Epilogue"
+            ]
+        );
+    }
+}
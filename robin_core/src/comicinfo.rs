@@ -0,0 +1,135 @@
+use chrono::{Datelike, NaiveDate};
+use regex::Regex;
+
+/// Name of the metadata file inside a chapter folder, read by most comic readers.
+pub const COMIC_INFO_FILE: &str = "ComicInfo.xml";
+
+/// Chapter metadata written as `ComicInfo.xml` (Anansi schema v2).
+#[derive(Debug, Clone, Default)]
+pub struct ComicInfo {
+    pub series: String,
+    pub title: String,
+    pub number: Option<f64>,
+    pub volume: Option<f64>,
+    pub writer: String,
+    pub web: String,
+    pub language: String,
+    pub page_count: usize,
+    pub release_date: Option<NaiveDate>,
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+impl ComicInfo {
+    pub fn to_xml(&self) -> String {
+        let mut fields = vec![
+            ("Series", escape(self.series.trim())),
+            ("Title", escape(self.title.trim())),
+        ];
+
+        if let Some(number) = self.number {
+            fields.push(("Number", number.to_string()));
+        }
+        if let Some(volume) = self.volume {
+            fields.push(("Volume", volume.to_string()));
+        }
+        if let Some(date) = self.release_date {
+            fields.push(("Year", date.year().to_string()));
+            fields.push(("Month", date.month().to_string()));
+            fields.push(("Day", date.day().to_string()));
+        }
+
+        fields.push(("Writer", escape(self.writer.trim())));
+        fields.push(("Web", escape(&self.web)));
+        fields.push(("LanguageISO", escape(&self.language)));
+        if self.page_count > 0 {
+            fields.push(("PageCount", self.page_count.to_string()));
+        }
+
+        let mut xml = String::from(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+             <ComicInfo xmlns:xsd=\"http://www.w3.org/2001/XMLSchema\" \
+             xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">\n",
+        );
+        for (tag, value) in fields.into_iter().filter(|(_, v)| !v.is_empty()) {
+            xml.push_str(&format!("  <{tag}>{value}</{tag}>\n"));
+        }
+        xml.push_str("</ComicInfo>\n");
+
+        xml
+    }
+}
+
+/// `xml` with another page count, for pages taken out after it was written.
+pub fn with_page_count(xml: &str, page_count: usize) -> String {
+    let re = Regex::new(r"\s*<PageCount>\d*</PageCount>").unwrap();
+    let xml = re.replace(xml, "");
+    if page_count == 0 {
+        return xml.into_owned();
+    }
+
+    // Last field, like in `to_xml`
+    xml.replacen(
+        "\n</ComicInfo>",
+        &format!("\n  <PageCount>{}</PageCount>\n</ComicInfo>", page_count),
+        1,
+    )
+}
+
+#[cfg(test)]
+mod comicinfo_tests {
+    use super::*;
+
+    #[test]
+    fn test_to_xml() {
+        let info = ComicInfo {
+            series: "Tom & Jerry".into(),
+            title: "Bölüm 10.5".into(),
+            number: Some(10.5),
+            web: "https://example.com/chapter-10-5".into(),
+            language: "tr".into(),
+            page_count: 12,
+            release_date: NaiveDate::from_ymd_opt(2024, 3, 12),
+            ..Default::default()
+        };
+        let xml = info.to_xml();
+
+        assert!(xml.contains("<Series>Tom &amp; Jerry</Series>"));
+        assert!(xml.contains("<Number>10.5</Number>"));
+        assert!(xml.contains("<Year>2024</Year>\n  <Month>3</Month>\n  <Day>12</Day>"));
+        assert!(xml.contains("<PageCount>12</PageCount>"));
+        // Empty fields are left out
+        assert!(!xml.contains("<Writer>"));
+        assert!(!xml.contains("<Volume>"));
+        assert!(xml.ends_with("</ComicInfo>\n"));
+    }
+
+    #[test]
+    fn test_with_page_count() {
+        let info = ComicInfo {
+            series: "uwu".into(),
+            page_count: 12,
+            ..Default::default()
+        };
+        let xml = with_page_count(&info.to_xml(), 10);
+        assert_eq!(
+            xml,
+            ComicInfo {
+                page_count: 10,
+                ..info.clone()
+            }
+            .to_xml()
+        );
+        let no_pages = ComicInfo {
+            page_count: 0,
+            ..info
+        };
+        assert_eq!(with_page_count(&xml, 0), no_pages.to_xml());
+    }
+}
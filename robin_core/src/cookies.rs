@@ -0,0 +1,437 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context};
+use cookie_store::{Cookie, CookieDomain, CookieExpiration, CookieStore, RawCookie};
+use reqwest::Url;
+use serde::Deserialize;
+use tempfile::NamedTempFile;
+use time::OffsetDateTime;
+
+/// Name of the cookie jar in the robin data folder.
+pub const JAR_FILE: &str = "cookies.json";
+
+/// A cookie as browsers export it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportedCookie {
+    /// Without the leading dot
+    pub domain: String,
+    pub include_subdomains: bool,
+    pub path: String,
+    pub secure: bool,
+    pub http_only: bool,
+    /// Unix time, a session cookie when `None`
+    pub expires: Option<i64>,
+    pub name: String,
+    pub value: String,
+}
+
+/// `domain` itself or one of its subdomains.
+pub fn domain_matches(cookie_domain: &str, domain: &str) -> bool {
+    let cookie_domain = cookie_domain.trim_start_matches('.');
+    let domain = domain.trim_start_matches('.');
+    cookie_domain == domain || cookie_domain.ends_with(&format!(".{}", domain))
+}
+
+fn netscape_bool(field: &str) -> bool {
+    field.eq_ignore_ascii_case("true")
+}
+
+/// Parses a Netscape `cookies.txt`, the format of curl, wget and most browser extensions.
+pub fn parse_netscape(text: &str) -> anyhow::Result<Vec<ExportedCookie>> {
+    let mut cookies = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        let (line, http_only) = match line.strip_prefix("#HttpOnly_") {
+            Some(line) => (line, true),
+            None => (line, false),
+        };
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields = line.split('\t').collect::<Vec<_>>();
+        if fields.len() != 7 {
+            bail!("line {}: expected 7 tab separated fields", i + 1);
+        }
+
+        let expires = fields[4]
+            .parse::<i64>()
+            .with_context(|| format!("line {}: bad expiry \"{}\"", i + 1, fields[4]))?;
+
+        cookies.push(ExportedCookie {
+            domain: fields[0].trim_start_matches('.').to_string(),
+            include_subdomains: netscape_bool(fields[1]),
+            path: fields[2].to_string(),
+            secure: netscape_bool(fields[3]),
+            http_only,
+            expires: (expires > 0).then_some(expires),
+            name: fields[5].to_string(),
+            value: fields[6].to_string(),
+        });
+    }
+
+    Ok(cookies)
+}
+
+fn root_path() -> String {
+    "/".to_string()
+}
+
+// Cookie-Editor, EditThisCookie and Selenium style
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonCookie {
+    domain: String,
+    name: String,
+    value: String,
+    #[serde(default = "root_path")]
+    path: String,
+    #[serde(default)]
+    secure: bool,
+    #[serde(default)]
+    http_only: bool,
+    #[serde(default)]
+    host_only: bool,
+    #[serde(default, alias = "expires", alias = "expiry")]
+    expiration_date: Option<f64>,
+}
+
+/// Parses a JSON array of cookies, as exported by browser extensions.
+pub fn parse_json(text: &str) -> anyhow::Result<Vec<ExportedCookie>> {
+    let cookies = serde_json::from_str::<Vec<JsonCookie>>(text)?
+        .into_iter()
+        .map(|c| ExportedCookie {
+            include_subdomains: !c.host_only,
+            domain: c.domain.trim_start_matches('.').to_string(),
+            path: c.path,
+            secure: c.secure,
+            http_only: c.http_only,
+            expires: c.expiration_date.map(|e| e as i64),
+            name: c.name,
+            value: c.value,
+        })
+        .collect();
+
+    Ok(cookies)
+}
+
+/// Either format, JSON when it looks like JSON.
+pub fn parse_cookies(text: &str) -> anyhow::Result<Vec<ExportedCookie>> {
+    match text.trim_start().starts_with('[') {
+        true => parse_json(text),
+        false => parse_netscape(text),
+    }
+}
+
+/// Netscape `cookies.txt` of some cookies.
+pub fn to_netscape(cookies: &[ExportedCookie]) -> String {
+    let mut buff = String::from("# Netscape HTTP Cookie File\n");
+    let flag = |b: bool| if b { "TRUE" } else { "FALSE" };
+
+    for c in cookies {
+        buff.push_str(&format!(
+            "{}{}{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+            if c.http_only { "#HttpOnly_" } else { "" },
+            if c.include_subdomains { "." } else { "" },
+            c.domain,
+            flag(c.include_subdomains),
+            c.path,
+            flag(c.secure),
+            c.expires.unwrap_or(0),
+            c.name,
+            c.value
+        ));
+    }
+
+    buff
+}
+
+impl ExportedCookie {
+    // The jar only takes cookies along with the URL that "sent" them
+    fn url(&self) -> anyhow::Result<Url> {
+        Ok(Url::parse(&format!(
+            "https://{}{}",
+            self.domain, self.path
+        ))?)
+    }
+
+    fn to_raw(&self) -> anyhow::Result<RawCookie<'static>> {
+        let mut raw = RawCookie::build((self.name.clone(), self.value.clone()))
+            .path(self.path.clone())
+            .secure(self.secure)
+            .http_only(self.http_only);
+
+        // Without a Domain attribute the cookie is host-only
+        if self.include_subdomains {
+            raw = raw.domain(self.domain.clone());
+        }
+        if let Some(expires) = self.expires {
+            raw = raw.expires(OffsetDateTime::from_unix_timestamp(expires)?);
+        }
+
+        Ok(raw.build())
+    }
+}
+
+/// Adds cookies to the jar, only the ones of `domain` (and its subdomains) if given.
+/// Returns how many went in, expired cookies are skipped.
+pub fn import(
+    store: &mut CookieStore,
+    cookies: &[ExportedCookie],
+    domain: Option<&str>,
+) -> anyhow::Result<usize> {
+    let mut count = 0;
+    let now = OffsetDateTime::now_utc().unix_timestamp();
+
+    for c in cookies {
+        if domain.is_some_and(|d| !domain_matches(&c.domain, d)) {
+            continue;
+        }
+        if c.expires.is_some_and(|e| e <= now) {
+            continue;
+        }
+
+        store
+            .insert_raw(&c.to_raw()?, &c.url()?)
+            .map_err(|e| anyhow!("cookie {} of {}: {}", c.name, c.domain, e))?;
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+/// Unexpired cookies of the jar, only the ones of `domain` if given, sorted by domain and name.
+pub fn jar_cookies(store: &CookieStore, domain: Option<&str>) -> Vec<ExportedCookie> {
+    let mut cookies = store
+        .iter_unexpired()
+        .map(|c| ExportedCookie {
+            domain: String::from(&c.domain),
+            include_subdomains: matches!(c.domain, CookieDomain::Suffix(_)),
+            path: c.path.to_string(),
+            secure: c.secure().unwrap_or(false),
+            http_only: c.http_only().unwrap_or(false),
+            expires: match c.expires {
+                CookieExpiration::AtUtc(at) => Some(at.unix_timestamp()),
+                CookieExpiration::SessionEnd => None,
+            },
+            name: c.name().to_string(),
+            value: c.value().to_string(),
+        })
+        .filter(|c| domain.is_none_or(|d| domain_matches(&c.domain, d)))
+        .collect::<Vec<_>>();
+
+    cookies.sort_by(|a, b| (&a.domain, &a.name).cmp(&(&b.domain, &b.name)));
+    cookies
+}
+
+/// Removes the cookies of `domain`, or all of them. Returns how many were removed.
+pub fn clear(store: &mut CookieStore, domain: Option<&str>) -> usize {
+    let keys = store
+        .iter_any()
+        .filter(|c| domain.is_none_or(|d| domain_matches(&String::from(&c.domain), d)))
+        .map(|c| {
+            (
+                String::from(&c.domain),
+                c.path.to_string(),
+                c.name().to_string(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    for (domain, path, name) in &keys {
+        store.remove(domain, path, name);
+    }
+
+    keys.len()
+}
+
+/// Creates (or truncates) a file only the user can read, cookies are as good as passwords.
+pub fn create_private(path: &Path) -> std::io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        let file = options.open(path)?;
+        // The mode only applies to new files
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        Ok(file)
+    }
+
+    #[cfg(not(unix))]
+    options.open(path)
+}
+
+/// The jar saved at `path`, an empty one if there is no file yet.
+pub fn load_jar(path: &Path) -> anyhow::Result<CookieStore> {
+    if !path.exists() {
+        return Ok(CookieStore::default());
+    }
+
+    let file = File::open(path)?;
+    CookieStore::load_json(BufReader::new(file))
+        .map_err(|e| anyhow!("bad cookie jar {}: {}", path.display(), e))
+}
+
+/// Saves the unexpired cookies of the jar, session cookies included since imported ones
+/// are often session cookies.
+pub fn save_jar(store: &CookieStore, path: &Path) -> anyhow::Result<()> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    fs::create_dir_all(parent)?;
+
+    // A temp file of its own so concurrent runs don't clash, only the user can read it
+    let partial = NamedTempFile::new_in(parent)?;
+    let mut writer = BufWriter::new(partial.as_file());
+    for cookie in store.iter_unexpired() {
+        writeln!(writer, "{}", serde_json::to_string(cookie)?)?;
+    }
+    writer.flush()?;
+    drop(writer);
+    partial
+        .persist(path)
+        .with_context(|| format!("can't save cookie jar {}", path.display()))?;
+
+    Ok(())
+}
+
+fn cookie_key(c: &Cookie) -> (String, String, String) {
+    (
+        String::from(&c.domain),
+        c.path.to_string(),
+        c.name().to_string(),
+    )
+}
+
+/// Saves what the run changed in `store` since `loaded` (the jar it started from) on top of
+/// the jar as it is now, so an import or clear done meanwhile isn't undone.
+pub fn merge_jar(store: &CookieStore, loaded: &CookieStore, path: &Path) -> anyhow::Result<()> {
+    let mut cookies = load_jar(path)?
+        .iter_any()
+        .map(|c| (cookie_key(c), c.clone()))
+        .collect::<HashMap<_, _>>();
+
+    // Set by the sites during the run
+    for c in store.iter_any() {
+        let (domain, path, name) = cookie_key(c);
+        if loaded.get_any(&domain, &path, &name) != Some(c) {
+            cookies.insert(cookie_key(c), c.clone());
+        }
+    }
+
+    // Removed by the sites, unless the jar got a new one meanwhile
+    for c in loaded.iter_any() {
+        let (domain, path, name) = cookie_key(c);
+        if !store.contains_any(&domain, &path, &name) && cookies.get(&cookie_key(c)) == Some(c) {
+            cookies.remove(&cookie_key(c));
+        }
+    }
+
+    let jar = CookieStore::from_cookies(cookies.into_values().map(anyhow::Ok), false)?;
+    save_jar(&jar, path)
+}
+
+#[cfg(test)]
+mod cookies_tests {
+    use super::*;
+
+    const COOKIES_TXT: &str = "# Netscape HTTP Cookie File\n\
+        .shijiescans.com\tTRUE\t/\tTRUE\t4102444800\tcf_clearance\tabc\n\
+        #HttpOnly_novelfull.com\tFALSE\t/\tFALSE\t0\tsession\tdef\n\
+        \n\
+        .example.com\tTRUE\t/\tFALSE\t4102444800\tother\tghi\n";
+
+    #[test]
+    fn test_netscape_round_trip() {
+        let cookies = parse_netscape(COOKIES_TXT).unwrap();
+        assert_eq!(cookies.len(), 3);
+        assert_eq!(cookies[0].domain, "shijiescans.com");
+        assert!(cookies[0].include_subdomains && cookies[0].secure);
+        assert_eq!(cookies[1].expires, None);
+        assert!(cookies[1].http_only && !cookies[1].include_subdomains);
+
+        assert_eq!(parse_cookies(&to_netscape(&cookies)).unwrap(), cookies);
+        assert!(parse_netscape("example.com\tTRUE\t/").is_err());
+    }
+
+    #[test]
+    fn test_json() {
+        let json = r#"[{"domain": ".shijiescans.com", "name": "cf_clearance", "value": "abc",
+            "hostOnly": false, "secure": true, "expirationDate": 4102444800.5}]"#;
+        let cookies = parse_cookies(json).unwrap();
+        assert_eq!(cookies, parse_netscape(COOKIES_TXT).unwrap()[..1]);
+    }
+
+    #[test]
+    fn test_jar() {
+        let mut store = CookieStore::default();
+        let cookies = parse_netscape(COOKIES_TXT).unwrap();
+        assert_eq!(
+            import(&mut store, &cookies, Some("shijiescans.com")).unwrap(),
+            1
+        );
+        assert_eq!(import(&mut store, &cookies, None).unwrap(), 3);
+
+        let url = Url::parse("https://www.shijiescans.com/series/uwu/").unwrap();
+        assert_eq!(store.get_request_values(&url).count(), 1);
+        assert_eq!(jar_cookies(&store, None).len(), 3);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("robin").join(JAR_FILE);
+        save_jar(&store, &path).unwrap();
+        let saved = jar_cookies(&store, None);
+        let mut store = load_jar(&path).unwrap();
+        assert_eq!(jar_cookies(&store, None), saved);
+        // Session cookie included, and no temp file left behind
+        assert_eq!(jar_cookies(&store, Some("novelfull.com"))[0].expires, None);
+        assert_eq!(fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        assert_eq!(jar_cookies(&store, Some("novelfull.com")).len(), 1);
+
+        assert_eq!(clear(&mut store, Some("example.com")), 1);
+        assert_eq!(jar_cookies(&store, None).len(), 2);
+        assert_eq!(clear(&mut store, None), 2);
+    }
+
+    #[test]
+    fn test_merge_jar() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(JAR_FILE);
+        let cookies = parse_netscape(COOKIES_TXT).unwrap();
+
+        let mut store = CookieStore::default();
+        import(&mut store, &cookies[..1], None).unwrap();
+        save_jar(&store, &path).unwrap();
+
+        // The run starts and a site sets a cookie
+        let loaded = load_jar(&path).unwrap();
+        let mut run = loaded.clone();
+        import(&mut run, &cookies[1..2], None).unwrap();
+
+        // Meanwhile the jar is cleared and gets another cookie
+        let mut jar = load_jar(&path).unwrap();
+        clear(&mut jar, None);
+        import(&mut jar, &cookies[2..], None).unwrap();
+        save_jar(&jar, &path).unwrap();
+
+        merge_jar(&run, &loaded, &path).unwrap();
+        let names = jar_cookies(&load_jar(&path).unwrap(), None)
+            .into_iter()
+            .map(|c| c.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["other", "session"]);
+    }
+}
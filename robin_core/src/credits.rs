@@ -0,0 +1,298 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use image::imageops::FilterType;
+use image::DynamicImage;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::comicinfo::{with_page_count, COMIC_INFO_FILE};
+use crate::manifest::{hash_reader, manifest_path, Manifest};
+
+/// Folder (inside the download folder) that moved aside pages go to.
+pub const SKIPPED_DIR: &str = "_skipped";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CreditAction {
+    /// Delete detected pages.
+    #[default]
+    Exclude,
+    /// Move detected pages into `SKIPPED_DIR`.
+    MoveAside,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CreditOptions {
+    /// A page is a credit page if it shows up in at least this many chapters. 0 disables it.
+    pub min_chapters: usize,
+    /// Hashes of images that are always dropped.
+    pub blocklist: Vec<u64>,
+    /// Maximum number of different bits for two hashes to count as the same image.
+    pub max_distance: u32,
+    pub action: CreditAction,
+}
+
+impl Default for CreditOptions {
+    fn default() -> Self {
+        CreditOptions {
+            min_chapters: 3,
+            blocklist: Vec::new(),
+            max_distance: 6,
+            action: CreditAction::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SkippedPage {
+    /// Path relative to the download folder
+    pub path: PathBuf,
+    pub reason: &'static str,
+}
+
+/// Difference hash of an image. Returns `None` for blank images (e.g. white webtoon strips),
+/// those would all look the same but are not credit pages.
+pub fn dhash(img: &DynamicImage) -> Option<u64> {
+    let small = img.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+
+    let min = small.pixels().map(|p| p.0[0]).min().unwrap_or(0);
+    let max = small.pixels().map(|p| p.0[0]).max().unwrap_or(0);
+    if max - min < 8 {
+        return None;
+    }
+
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if small.get_pixel(x, y).0[0] > small.get_pixel(x + 1, y).0[0] {
+                hash |= 1;
+            }
+        }
+    }
+
+    Some(hash)
+}
+
+pub fn hamming(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+fn hash_file(path: &Path) -> Option<u64> {
+    let bytes = fs::read(path).ok()?;
+    dhash(&image::load_from_memory(&bytes).ok()?)
+}
+
+/// Hashes every image in a blocklist folder.
+pub fn load_blocklist(dir: impl AsRef<Path>) -> anyhow::Result<Vec<u64>> {
+    let files = fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_file())
+        .collect::<Vec<PathBuf>>();
+
+    Ok(files.par_iter().filter_map(|p| hash_file(p)).collect())
+}
+
+/// Finds credit, recruitment and ad pages in a downloaded serie and deletes or moves them aside.
+///
+/// `root` must contain one folder per chapter. Returns the skipped pages.
+pub fn drop_credit_pages(
+    root: impl AsRef<Path>,
+    opts: &CreditOptions,
+) -> anyhow::Result<Vec<SkippedPage>> {
+    let root = root.as_ref();
+
+    let mut pages: Vec<(usize, PathBuf)> = Vec::new();
+    let mut chapter_dirs = fs::read_dir(root)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_dir() && !p.ends_with(SKIPPED_DIR))
+        // Hidden folders are staging leftovers
+        .filter(|p| {
+            !p.file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .starts_with('.')
+        })
+        .collect::<Vec<PathBuf>>();
+    chapter_dirs.sort();
+
+    for (i, dir) in chapter_dirs.iter().enumerate() {
+        for entry in fs::read_dir(dir)?.filter_map(|e| e.ok()) {
+            if entry.path().is_file() {
+                pages.push((i, entry.path()));
+            }
+        }
+    }
+    pages.sort();
+
+    let hashes = pages
+        .par_iter()
+        .map(|(_, p)| hash_file(p))
+        .collect::<Vec<Option<u64>>>();
+
+    let mut skipped = Vec::new();
+
+    for (idx, (_, path)) in pages.iter().enumerate() {
+        let Some(hash) = hashes[idx] else {
+            continue;
+        };
+
+        let reason = if opts
+            .blocklist
+            .iter()
+            .any(|b| hamming(*b, hash) <= opts.max_distance)
+        {
+            Some("blocklist")
+        } else if opts.min_chapters > 0 {
+            let chapters = pages
+                .iter()
+                .zip(hashes.iter())
+                .filter_map(|((c, _), h)| h.map(|h| (c, h)))
+                .filter(|(_, h)| hamming(*h, hash) <= opts.max_distance)
+                .map(|(c, _)| *c)
+                .collect::<HashSet<usize>>();
+
+            (chapters.len() >= opts.min_chapters).then_some("repeated")
+        } else {
+            None
+        };
+
+        if let Some(reason) = reason {
+            skipped.push(SkippedPage {
+                path: path.strip_prefix(root)?.to_path_buf(),
+                reason,
+            });
+        }
+    }
+
+    for page in &skipped {
+        let from = root.join(&page.path);
+
+        match opts.action {
+            CreditAction::Exclude => fs::remove_file(&from)?,
+            CreditAction::MoveAside => {
+                let to = root.join(SKIPPED_DIR).join(&page.path);
+                fs::create_dir_all(to.parent().unwrap())?;
+                fs::rename(&from, &to)?;
+            }
+        }
+    }
+
+    for chapter in skipped_chapters(&skipped) {
+        update_page_count(&root.join(chapter))?;
+    }
+
+    Ok(skipped)
+}
+
+// Chapter folders that lost pages, relative to the serie
+fn skipped_chapters(skipped: &[SkippedPage]) -> HashSet<&Path> {
+    skipped.iter().filter_map(|p| p.path.parent()).collect()
+}
+
+// ComicInfo.xml counts the pages of its chapter
+fn update_page_count(dir: &Path) -> anyhow::Result<()> {
+    let path = dir.join(COMIC_INFO_FILE);
+    if !path.exists() {
+        return Ok(());
+    }
+
+    let page_count = fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_file())
+        .filter(|e| {
+            let name = e.file_name().to_string_lossy().into_owned();
+            name != COMIC_INFO_FILE && !name.starts_with('.')
+        })
+        .count();
+    fs::write(
+        &path,
+        with_page_count(&fs::read_to_string(&path)?, page_count),
+    )?;
+
+    Ok(())
+}
+
+/// `drop_credit_pages` on a serie with a manifest: the pages are taken out of it and the
+/// options kept there, so `robin verify --repair` cleans repaired chapters the same way.
+pub fn drop_credits_in_serie(
+    root: &Path,
+    opts: &CreditOptions,
+) -> anyhow::Result<Vec<SkippedPage>> {
+    let skipped = drop_credit_pages(root, opts)?;
+
+    let mut manifest = Manifest::load(root)?;
+    for page in &skipped {
+        manifest.remove(&manifest_path(&page.path));
+    }
+    // Their ComicInfo.xml was rewritten
+    for chapter in skipped_chapters(&skipped) {
+        let path = manifest_path(&chapter.join(COMIC_INFO_FILE));
+        if let Some(entry) = manifest.files.iter_mut().find(|e| e.path == path) {
+            (entry.sha256, entry.size) = hash_reader(fs::File::open(root.join(&path))?)?;
+        }
+    }
+    manifest.credits = Some(opts.clone());
+    manifest.save(root)?;
+
+    Ok(skipped)
+}
+
+#[cfg(test)]
+mod credits_tests {
+    use super::*;
+    use crate::comicinfo::ComicInfo;
+    use image::{Rgb, RgbImage};
+
+    fn pattern(seed: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(90, 80, |x, y| {
+            let v = ((x * seed + y * (seed + 3)) % 251) as u8;
+            Rgb([v, v, v])
+        }))
+    }
+
+    #[test]
+    fn test_blank_image_has_no_hash() {
+        let img = DynamicImage::ImageRgb8(RgbImage::from_pixel(50, 50, Rgb([255, 255, 255])));
+        assert_eq!(dhash(&img), None);
+    }
+
+    #[test]
+    fn test_repeated_pages_are_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+
+        for ch in 0..3 {
+            let ch_dir = dir.path().join(format!("Bölüm {}", ch));
+            fs::create_dir(&ch_dir).unwrap();
+            pattern(5).save(ch_dir.join("0000.png")).unwrap();
+            pattern(17 + ch).save(ch_dir.join("0001.png")).unwrap();
+            let info = ComicInfo {
+                page_count: 2,
+                ..Default::default()
+            };
+            fs::write(ch_dir.join(COMIC_INFO_FILE), info.to_xml()).unwrap();
+        }
+
+        let opts = CreditOptions {
+            action: CreditAction::MoveAside,
+            ..Default::default()
+        };
+        let skipped = drop_credit_pages(dir.path(), &opts).unwrap();
+
+        assert_eq!(skipped.len(), 3);
+        assert!(skipped.iter().all(|s| s.path.ends_with("0000.png")));
+        assert!(dir
+            .path()
+            .join(SKIPPED_DIR)
+            .join("Bölüm 0")
+            .join("0000.png")
+            .exists());
+
+        let xml = fs::read_to_string(dir.path().join("Bölüm 1").join(COMIC_INFO_FILE)).unwrap();
+        assert!(xml.contains("<PageCount>1</PageCount>"));
+    }
+}
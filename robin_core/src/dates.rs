@@ -0,0 +1,240 @@
+use chrono::{Days, Months, NaiveDate};
+
+const TURKISH_MONTHS: [&str; 12] = [
+    "ocak", "şubat", "mart", "nisan", "mayıs", "haziran", "temmuz", "ağustos", "eylül", "ekim",
+    "kasım", "aralık",
+];
+
+const ENGLISH_MONTHS: [&str; 12] = [
+    "january",
+    "february",
+    "march",
+    "april",
+    "may",
+    "june",
+    "july",
+    "august",
+    "september",
+    "october",
+    "november",
+    "december",
+];
+
+enum Unit {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+// Sites are not consistent with Turkish letters ("Subat", "Mayis"...)
+fn fold_turkish(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            'ş' => 's',
+            'ı' => 'i',
+            'ğ' => 'g',
+            'ü' => 'u',
+            'ö' => 'o',
+            'ç' => 'c',
+            c => c,
+        })
+        // "İ" lowercases to "i" and a combining dot
+        .filter(|c| *c != '\u{307}')
+        .collect()
+}
+
+fn month_number(word: &str, lang: &str) -> Option<u32> {
+    let months = match lang {
+        "tr" => &TURKISH_MONTHS,
+        _ => &ENGLISH_MONTHS,
+    };
+    let word = fold_turkish(&word.to_lowercase());
+
+    // Full names or abbreviations like "Mar", "Sept", "Şub"
+    months
+        .iter()
+        .position(|m| {
+            let m = fold_turkish(m);
+            m == word || (word.chars().count() >= 3 && m.starts_with(&word))
+        })
+        .map(|i| i as u32 + 1)
+}
+
+fn parse_absolute(text: &str, lang: &str) -> Option<NaiveDate> {
+    let numeric_formats: &[&str] = match lang {
+        "en" => &["%Y-%m-%d", "%m/%d/%Y", "%d.%m.%Y"],
+        _ => &["%Y-%m-%d", "%d/%m/%Y", "%d.%m.%Y"],
+    };
+    for format in numeric_formats {
+        if let Ok(date) = NaiveDate::parse_from_str(text, format) {
+            return Some(date);
+        }
+    }
+
+    // "Mart 12, 2024", "12 March 2024", "Mar 12th 2024"
+    let mut month = None;
+    let mut numbers = Vec::new();
+
+    for token in text
+        .split(|c: char| c.is_whitespace() || c == ',' || c == '.')
+        .filter(|t| !t.is_empty())
+    {
+        let digits = token.trim_end_matches(|c: char| c.is_alphabetic());
+        match digits.parse::<u32>() {
+            Ok(n) if !digits.is_empty() => numbers.push(n),
+            _ => month = month.or(month_number(token, lang)),
+        }
+    }
+
+    let (day, year) = match numbers[..] {
+        [a, b] if b > 31 => (a, b),
+        [a, b] if a > 31 => (b, a),
+        _ => return None,
+    };
+
+    NaiveDate::from_ymd_opt(year as i32, month?, day)
+}
+
+fn parse_relative(text: &str, lang: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let text = text.to_lowercase();
+
+    let (today_words, yesterday_words, ago, one_words): (&[&str], &[&str], &str, &[&str]) =
+        match lang {
+            "tr" => (&["bugün", "şimdi"], &["dün"], "önce", &["bir"]),
+            _ => (
+                &["today", "just now", "now"],
+                &["yesterday"],
+                "ago",
+                &["a", "an", "one"],
+            ),
+        };
+
+    if today_words.contains(&text.trim()) {
+        return Some(today);
+    }
+    if yesterday_words.contains(&text.trim()) {
+        return today.checked_sub_days(Days::new(1));
+    }
+    if !text.contains(ago) {
+        return None;
+    }
+
+    let mut amount = None;
+    let mut unit = None;
+
+    for token in text.split_whitespace() {
+        if let Ok(n) = token.parse::<u32>() {
+            amount = amount.or(Some(n));
+        } else if one_words.contains(&token) {
+            amount = amount.or(Some(1));
+        } else {
+            let token = token.trim_end_matches('s');
+            unit = unit.or(match (lang, token) {
+                ("tr", "saniye" | "dakika" | "saat") => Some((Unit::Day, 0)),
+                ("tr", "gün") => Some((Unit::Day, 1)),
+                ("tr", "hafta") => Some((Unit::Week, 1)),
+                ("tr", "ay") => Some((Unit::Month, 1)),
+                ("tr", "yıl") => Some((Unit::Year, 1)),
+                (_, "second" | "sec" | "minute" | "min" | "hour") => Some((Unit::Day, 0)),
+                (_, "day") => Some((Unit::Day, 1)),
+                (_, "week") => Some((Unit::Week, 1)),
+                (_, "month") => Some((Unit::Month, 1)),
+                (_, "year") => Some((Unit::Year, 1)),
+                _ => None,
+            });
+        }
+    }
+
+    let amount = amount?;
+    match unit? {
+        // Less than a day ago is still today
+        (Unit::Day, 0) => Some(today),
+        (Unit::Day, _) => today.checked_sub_days(Days::new(amount as u64)),
+        (Unit::Week, _) => today.checked_sub_days(Days::new(amount as u64 * 7)),
+        (Unit::Month, _) => today.checked_sub_months(Months::new(amount)),
+        (Unit::Year, _) => today.checked_sub_months(Months::new(amount * 12)),
+    }
+}
+
+/// Parses a release date as shown by a source in the language `lang` ("tr", "en"...).
+/// Understands month names ("Mart 12, 2024", "12 March 2024"), numeric dates and
+/// relative dates ("2 gün önce", "3 days ago"), which are counted back from `today`.
+pub fn parse_date(text: &str, lang: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let text = text.trim();
+
+    parse_relative(text, lang, today).or_else(|| parse_absolute(text, lang))
+}
+
+/// Today as a date, for `parse_date`.
+pub fn local_today() -> NaiveDate {
+    chrono::Local::now().date_naive()
+}
+
+/// Whether a release date is inside `since..=until`. Unknown dates only pass without limits.
+pub fn in_range(
+    date: Option<NaiveDate>,
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+) -> bool {
+    match date {
+        Some(date) => {
+            since.unwrap_or(NaiveDate::MIN) <= date && date <= until.unwrap_or(NaiveDate::MAX)
+        }
+        None => since.is_none() && until.is_none(),
+    }
+}
+
+#[cfg(test)]
+mod dates_tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_month_names() {
+        let tr = |text| parse_date(text, "tr", date(2024, 6, 1));
+        let en = |text| parse_date(text, "en", date(2024, 6, 1));
+
+        assert_eq!(tr("Mart 12, 2024"), Some(date(2024, 3, 12)));
+        assert_eq!(tr("12 Ağustos 2023"), Some(date(2023, 8, 12)));
+        assert_eq!(tr("Subat 3, 2024"), Some(date(2024, 2, 3)));
+        assert_eq!(tr("NİSAN 7, 2024"), Some(date(2024, 4, 7)));
+        assert_eq!(tr("2 EKİM 2023"), Some(date(2023, 10, 2)));
+        assert_eq!(tr("EYLÜL 9, 2023"), Some(date(2023, 9, 9)));
+        assert_eq!(tr("05.01.2024"), Some(date(2024, 1, 5)));
+        assert_eq!(en("March 12, 2024"), Some(date(2024, 3, 12)));
+        assert_eq!(en("Sept 1st, 2023"), Some(date(2023, 9, 1)));
+        assert_eq!(en("2024-01-05"), Some(date(2024, 1, 5)));
+        assert_eq!(tr("Mart 40, 2024"), None);
+        assert_eq!(en("soon"), None);
+    }
+
+    #[test]
+    fn test_relative_dates() {
+        let tr = |text| parse_date(text, "tr", date(2024, 3, 31));
+        let en = |text| parse_date(text, "en", date(2024, 3, 31));
+
+        assert_eq!(tr("2 gün önce"), Some(date(2024, 3, 29)));
+        assert_eq!(tr("1 hafta önce"), Some(date(2024, 3, 24)));
+        assert_eq!(tr("bir ay önce"), Some(date(2024, 2, 29)));
+        assert_eq!(tr("5 saat önce"), Some(date(2024, 3, 31)));
+        assert_eq!(tr("Dün"), Some(date(2024, 3, 30)));
+        assert_eq!(en("3 days ago"), Some(date(2024, 3, 28)));
+        assert_eq!(en("a year ago"), Some(date(2023, 3, 31)));
+        assert_eq!(en("yesterday"), Some(date(2024, 3, 30)));
+    }
+
+    #[test]
+    fn test_in_range() {
+        let since = Some(date(2024, 1, 1));
+        let until = Some(date(2024, 1, 31));
+
+        assert!(in_range(Some(date(2024, 1, 1)), since, until));
+        assert!(!in_range(Some(date(2024, 2, 1)), since, until));
+        assert!(!in_range(None, since, None));
+        assert!(in_range(None, None, None));
+    }
+}
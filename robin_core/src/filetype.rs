@@ -0,0 +1,105 @@
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Response;
+
+/// `Content-Type` header of a response, if there is a readable one.
+pub fn content_type(response: &Response) -> Option<String> {
+    response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(String::from)
+}
+
+/// Extension for an image `Content-Type` header, parameters (`; charset=...`) are ignored.
+pub fn extension_from_content_type(content_type: &str) -> Option<&'static str> {
+    let mime = content_type.split(';').next()?.trim().to_ascii_lowercase();
+
+    match mime.as_str() {
+        "image/jpeg" | "image/jpg" | "image/pjpeg" => Some("jpg"),
+        "image/png" => Some("png"),
+        "image/gif" => Some("gif"),
+        "image/webp" => Some("webp"),
+        "image/avif" => Some("avif"),
+        _ => None,
+    }
+}
+
+/// Extension for a file by looking at its first bytes.
+pub fn sniff_extension(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("jpg")
+    } else if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("png")
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some("gif")
+    } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        Some("webp")
+    } else if bytes.len() >= 12
+        && &bytes[4..8] == b"ftyp"
+        && (&bytes[8..12] == b"avif" || &bytes[8..12] == b"avis")
+    {
+        Some("avif")
+    } else {
+        None
+    }
+}
+
+/// Picks a file extension from the `Content-Type` header, falling back to the magic bytes.
+/// Returns "bin" if neither of them is a known image type.
+pub fn detect_extension(content_type: Option<&str>, bytes: &[u8]) -> &'static str {
+    content_type
+        .and_then(extension_from_content_type)
+        .or_else(|| sniff_extension(bytes))
+        .unwrap_or("bin")
+}
+
+/// MIME type of an image extension.
+pub fn mime_type(ext: &str) -> Option<&'static str> {
+    match ext.to_ascii_lowercase().as_str() {
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "png" => Some("image/png"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        "avif" => Some("image/avif"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod filetype_tests {
+    use super::*;
+
+    #[test]
+    fn test_content_type() {
+        assert_eq!(extension_from_content_type("image/jpeg"), Some("jpg"));
+        assert_eq!(extension_from_content_type("Image/WebP; q=1"), Some("webp"));
+        assert_eq!(
+            extension_from_content_type("application/octet-stream"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_sniffing() {
+        assert_eq!(sniff_extension(&[0xFF, 0xD8, 0xFF, 0xE0]), Some("jpg"));
+        assert_eq!(sniff_extension(b"\x89PNG\r\n\x1a\n...."), Some("png"));
+        assert_eq!(
+            sniff_extension(b"RIFF\x00\x00\x00\x00WEBPVP8 "),
+            Some("webp")
+        );
+        assert_eq!(sniff_extension(b"\x00\x00\x00\x1cftypavif"), Some("avif"));
+        assert_eq!(sniff_extension(b"<html>"), None);
+    }
+
+    #[test]
+    fn test_detect_falls_back_to_magic_bytes() {
+        let png = b"\x89PNG\r\n\x1a\n....";
+        assert_eq!(
+            detect_extension(Some("application/octet-stream"), png),
+            "png"
+        );
+        assert_eq!(detect_extension(None, png), "png");
+        assert_eq!(detect_extension(Some("image/gif"), png), "gif");
+        assert_eq!(detect_extension(None, b"???"), "bin");
+    }
+}
@@ -1,4 +1,20 @@
 #![allow(refining_impl_trait)]
 pub mod utils;
 pub mod sources;
-pub mod matcher;
\ No newline at end of file
+pub mod matcher;
+pub mod credits;
+pub mod filetype;
+pub mod path_safety;
+pub mod naming;
+pub mod staging;
+pub mod processing;
+pub mod stitch;
+pub mod validation;
+pub mod manifest;
+pub mod selection;
+pub mod dates;
+pub mod comicinfo;
+pub mod chapter_number;
+pub mod chapter_check;
+pub mod net;
+pub mod cookies;
\ No newline at end of file
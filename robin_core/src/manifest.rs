@@ -0,0 +1,434 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+
+use anyhow::Context;
+use chrono::NaiveDate;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::credits::{CreditOptions, SKIPPED_DIR};
+use crate::naming::{NameTemplate, Templates};
+use crate::processing::ImageOptions;
+use crate::sources::DownloadOptions;
+use crate::stitch::StitchMode;
+use crate::utils::sha256_hex;
+
+/// Name of the manifest inside the serie folder.
+pub const MANIFEST_FILE: &str = "robin-manifest.json";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Path relative to the serie folder, always with `/`
+    pub path: String,
+    pub sha256: String,
+    pub size: u64,
+    /// Where the file was downloaded from
+    pub url: String,
+    pub chapter: Option<f64>,
+    #[serde(default)]
+    pub volume: Option<f64>,
+    /// Chapter page, used to download the chapter again
+    pub chapter_url: Option<String>,
+    /// Release date of the chapter, if the source shows it
+    #[serde(default)]
+    pub released: Option<NaiveDate>,
+    /// RFC 3339
+    pub downloaded_at: String,
+}
+
+impl ManifestEntry {
+    pub fn from_bytes(path: String, bytes: &[u8], url: String) -> ManifestEntry {
+        ManifestEntry {
+            path,
+            sha256: sha256_hex(bytes),
+            size: bytes.len() as u64,
+            url,
+            chapter: None,
+            volume: None,
+            chapter_url: None,
+            released: None,
+            downloaded_at: now(),
+        }
+    }
+}
+
+/// Checksums of every downloaded file of a serie.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    /// "manga" or "novel"
+    pub kind: String,
+    /// URL of the serie
+    pub source: String,
+    /// Naming templates the serie was downloaded with, re-downloads use the same names
+    pub templates: BTreeMap<String, String>,
+    /// Page processing of the download, repairs do it again
+    #[serde(default)]
+    pub image: Option<ImageOptions>,
+    #[serde(default)]
+    pub stitch: Option<StitchMode>,
+    /// Credit page cleaning done after the download, see `drop_credits_in_serie`
+    #[serde(default)]
+    pub credits: Option<CreditOptions>,
+    pub files: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    pub fn new(kind: &str, source: &str, opts: &DownloadOptions) -> Manifest {
+        let templates = &opts.templates;
+
+        Manifest {
+            kind: kind.to_string(),
+            source: source.to_string(),
+            templates: BTreeMap::from([
+                ("series".to_string(), templates.series.to_string()),
+                ("chapter".to_string(), templates.chapter.to_string()),
+                ("page".to_string(), templates.page.to_string()),
+            ]),
+            image: opts.image.clone(),
+            stitch: opts.stitch,
+            credits: None,
+            files: Vec::new(),
+        }
+    }
+
+    /// Reads the manifest of a serie folder.
+    pub fn load(dir: &Path) -> anyhow::Result<Manifest> {
+        let path = dir.join(MANIFEST_FILE);
+        let data = fs::read(&path).with_context(|| format!("can't read {}", path.display()))?;
+
+        Ok(serde_json::from_slice(&data)?)
+    }
+
+    /// Writes the manifest into a serie folder, replacing the old one with a rename.
+    pub fn save(&self, dir: &Path) -> anyhow::Result<()> {
+        let tmp = dir.join(format!(".{}.tmp", MANIFEST_FILE));
+        fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&tmp, dir.join(MANIFEST_FILE))?;
+
+        Ok(())
+    }
+
+    /// Adds entries, replacing older entries with the same path. Entries stay sorted by path.
+    pub fn insert(&mut self, entries: impl IntoIterator<Item = ManifestEntry>) {
+        for entry in entries {
+            self.files.retain(|e| e.path != entry.path);
+            self.files.push(entry);
+        }
+        self.files.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+
+    /// Removes every entry inside a chapter folder, before it is downloaded again.
+    pub fn remove_dir(&mut self, dir: &str) {
+        let prefix = format!("{}/", dir);
+        self.files.retain(|e| !e.path.starts_with(&prefix));
+    }
+
+    pub fn remove(&mut self, path: &str) {
+        self.files.retain(|e| e.path != path);
+    }
+
+    /// Top level chapter folders (manga) or files (novel) grouped by volume, in volume order.
+    /// Chapters without a volume are grouped under `None`, last.
+    pub fn volume_groups(&self) -> Vec<(Option<f64>, Vec<String>)> {
+        let mut groups: Vec<(Option<f64>, Vec<String>)> = Vec::new();
+
+        for entry in self.files.iter().filter(|e| e.chapter_url.is_some()) {
+            let top = entry.path.split('/').next().unwrap_or_default().to_string();
+
+            match groups
+                .iter_mut()
+                .find(|(volume, _)| *volume == entry.volume)
+            {
+                Some((_, items)) if items.contains(&top) => {}
+                Some((_, items)) => items.push(top),
+                None => groups.push((entry.volume, vec![top])),
+            }
+        }
+
+        groups.sort_by(|(a, _), (b, _)| match (a, b) {
+            (Some(a), Some(b)) => a.total_cmp(b),
+            (a, b) => a.is_none().cmp(&b.is_none()),
+        });
+
+        groups
+    }
+
+    /// The templates the serie was downloaded with, defaults for missing ones.
+    pub fn templates(&self) -> anyhow::Result<Templates> {
+        let mut templates = Templates::default();
+
+        for (key, template) in [
+            ("series", &mut templates.series),
+            ("chapter", &mut templates.chapter),
+            ("page", &mut templates.page),
+        ] {
+            if let Some(value) = self.templates.get(key) {
+                *template = NameTemplate::parse(value)?;
+            }
+        }
+
+        Ok(templates)
+    }
+
+    /// Options to download chapters again the way they were downloaded.
+    pub fn download_options(&self) -> anyhow::Result<DownloadOptions> {
+        Ok(DownloadOptions {
+            image: self.image.clone(),
+            stitch: self.stitch,
+            templates: self.templates()?,
+            ..Default::default()
+        })
+    }
+}
+
+/// Current time for `downloaded_at`.
+pub fn now() -> String {
+    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+}
+
+/// Manifest path of a file relative to the serie folder.
+pub fn manifest_path(relative: &Path) -> String {
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    pub checked: usize,
+    /// In the manifest but not on disk
+    pub missing: Vec<String>,
+    /// On disk but not in the manifest
+    pub extra: Vec<String>,
+    /// Different size or hash
+    pub corrupted: Vec<String>,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.missing.is_empty() && self.corrupted.is_empty()
+    }
+
+    /// Entries that have to be downloaded again.
+    pub fn broken<'a>(&self, manifest: &'a Manifest) -> Vec<&'a ManifestEntry> {
+        manifest
+            .files
+            .iter()
+            .filter(|e| self.missing.contains(&e.path) || self.corrupted.contains(&e.path))
+            .collect()
+    }
+}
+
+/// SHA-256 and size of everything `reader` gives.
+pub fn hash_reader(mut reader: impl io::Read) -> io::Result<(String, u64)> {
+    let mut hasher = Sha256::new();
+    let size = io::copy(&mut reader, &mut hasher)?;
+
+    Ok((format!("{:x}", hasher.finalize()), size))
+}
+
+/// Hash and size of files found for a manifest, by manifest path. `None` when the file
+/// could not be read, or was not hashed because the manifest doesn't know it.
+pub type FoundFiles = BTreeMap<String, Option<(String, u64)>>;
+
+/// Files of a serie folder. Only the ones in the manifest are hashed.
+pub fn scan_dir(dir: &Path, manifest: &Manifest) -> anyhow::Result<FoundFiles> {
+    let known = manifest
+        .files
+        .iter()
+        .map(|e| e.path.as_str())
+        .collect::<HashSet<&str>>();
+
+    let mut paths = Vec::new();
+    for ent in walkdir::WalkDir::new(dir)
+        .into_iter()
+        // Hidden files are markers and staging leftovers
+        .filter_entry(|e| e.depth() == 0 || !e.file_name().to_string_lossy().starts_with('.'))
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+    {
+        paths.push(manifest_path(ent.path().strip_prefix(dir)?));
+    }
+
+    Ok(paths
+        .into_par_iter()
+        .map(|path| {
+            let hash = match known.contains(path.as_str()) {
+                true => File::open(dir.join(&path)).and_then(hash_reader).ok(),
+                false => None,
+            };
+            (path, hash)
+        })
+        .collect())
+}
+
+/// Compares files found on disk or in an archive with the manifest.
+pub fn compare(manifest: &Manifest, found: &FoundFiles) -> VerifyReport {
+    let mut report = VerifyReport {
+        checked: manifest.files.len(),
+        ..Default::default()
+    };
+
+    for entry in &manifest.files {
+        match found.get(&entry.path) {
+            None => report.missing.push(entry.path.clone()),
+            Some(None) => report.corrupted.push(entry.path.clone()),
+            Some(Some((sha256, size))) => {
+                if *size != entry.size || *sha256 != entry.sha256 {
+                    report.corrupted.push(entry.path.clone());
+                }
+            }
+        }
+    }
+
+    let known = manifest
+        .files
+        .iter()
+        .map(|e| e.path.as_str())
+        .collect::<HashSet<&str>>();
+
+    report.extra = found
+        .keys()
+        .filter(|path| !known.contains(path.as_str()))
+        .filter(|path| *path != MANIFEST_FILE && !path.starts_with(&format!("{}/", SKIPPED_DIR)))
+        .cloned()
+        .collect();
+
+    report
+}
+
+/// Hashes every file of a serie folder again and compares it with the manifest.
+pub fn verify(dir: &Path) -> anyhow::Result<VerifyReport> {
+    let manifest = Manifest::load(dir)?;
+    Ok(compare(&manifest, &scan_dir(dir, &manifest)?))
+}
+
+#[cfg(test)]
+mod manifest_tests {
+    use super::*;
+
+    fn entry(dir: &Path, path: &str, bytes: &[u8]) -> ManifestEntry {
+        fs::create_dir_all(dir.join(path).parent().unwrap()).unwrap();
+        fs::write(dir.join(path), bytes).unwrap();
+
+        ManifestEntry {
+            chapter: Some(1.0),
+            chapter_url: Some("https://example.com/chapter-1".into()),
+            ..ManifestEntry::from_bytes(path.into(), bytes, "https://example.com/page".into())
+        }
+    }
+
+    #[test]
+    fn test_verify() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+
+        let mut manifest =
+            Manifest::new("manga", "https://example.com", &DownloadOptions::default());
+        manifest.insert([
+            entry(dir, "Chapter 1/0000.jpg", b"page 0"),
+            entry(dir, "Chapter 1/0001.jpg", b"page 1"),
+            entry(dir, "Chapter 1/0002.jpg", b"page 2"),
+        ]);
+        manifest.save(dir).unwrap();
+
+        assert!(verify(dir).unwrap().is_ok());
+
+        fs::remove_file(dir.join("Chapter 1/0000.jpg")).unwrap();
+        fs::write(dir.join("Chapter 1/0001.jpg"), b"page 9").unwrap();
+        fs::write(dir.join("Chapter 1/extra.jpg"), b"?").unwrap();
+        fs::write(dir.join("Chapter 1/.incomplete"), b"").unwrap();
+
+        let report = verify(dir).unwrap();
+        assert!(!report.is_ok());
+        assert_eq!(report.checked, 3);
+        assert_eq!(report.missing, ["Chapter 1/0000.jpg"]);
+        assert_eq!(report.corrupted, ["Chapter 1/0001.jpg"]);
+        assert_eq!(report.extra, ["Chapter 1/extra.jpg"]);
+        assert_eq!(report.broken(&manifest).len(), 2);
+    }
+
+    #[test]
+    fn test_insert_replaces_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+
+        let mut manifest =
+            Manifest::new("manga", "https://example.com", &DownloadOptions::default());
+        manifest.insert([
+            entry(dir, "b/0000.jpg", b"old"),
+            entry(dir, "a/0000.jpg", b"a"),
+        ]);
+        manifest.insert([entry(dir, "b/0000.jpg", b"new")]);
+
+        assert_eq!(manifest.files.len(), 2);
+        assert_eq!(manifest.files[0].path, "a/0000.jpg");
+        assert_eq!(manifest.files[1].sha256, sha256_hex(b"new"));
+
+        manifest.remove_dir("a");
+        assert_eq!(manifest.files.len(), 1);
+        assert_eq!(manifest.templates().unwrap().page.as_str(), "{page:04}");
+    }
+
+    #[test]
+    fn test_download_options_are_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let opts = DownloadOptions {
+            image: Some(ImageOptions {
+                max_width: Some(1080),
+                grayscale: true,
+                ..Default::default()
+            }),
+            stitch: Some(StitchMode::Split {
+                page_height: Some(1600),
+            }),
+            ..Default::default()
+        };
+
+        let mut manifest = Manifest::new("manga", "https://example.com", &opts);
+        manifest.credits = Some(CreditOptions::default());
+        manifest.save(dir.path()).unwrap();
+
+        let manifest = Manifest::load(dir.path()).unwrap();
+        let repair = manifest.download_options().unwrap();
+        assert_eq!(repair.image, opts.image);
+        assert_eq!(repair.stitch, opts.stitch);
+        assert_eq!(manifest.credits, Some(CreditOptions::default()));
+    }
+
+    #[test]
+    fn test_volume_groups() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+
+        let in_volume = |path: &str, volume: Option<f64>| ManifestEntry {
+            volume,
+            ..entry(dir, path, b"page")
+        };
+
+        let mut manifest =
+            Manifest::new("manga", "https://example.com", &DownloadOptions::default());
+        manifest.insert([
+            ManifestEntry::from_bytes("cover.jpg".into(), b"cover", "https://example.com".into()),
+            in_volume("Extra/0000.jpg", None),
+            in_volume("Ch 3/0000.jpg", Some(2.0)),
+            in_volume("Ch 1/0000.jpg", Some(1.0)),
+            in_volume("Ch 1/0001.jpg", Some(1.0)),
+            in_volume("Ch 2/0000.jpg", Some(1.0)),
+        ]);
+
+        assert_eq!(
+            manifest.volume_groups(),
+            [
+                (Some(1.0), vec!["Ch 1".to_string(), "Ch 2".to_string()]),
+                (Some(2.0), vec!["Ch 3".to_string()]),
+                (None, vec!["Extra".to_string()]),
+            ]
+        );
+    }
+}
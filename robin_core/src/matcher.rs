@@ -1,17 +1,119 @@
+use crate::net::{build_client, NetOptions};
 use crate::sources::*;
+use reqwest::Client;
 
-pub async fn match_manga(url: String, proxy: String) -> anyhow::Result<impl Serie> {
-    if url.starts_with("https://shijiescans.com") {
-        return Ok(shijie_turkish::ShijieTurkish::new(url, proxy).await?);
+const SOURCES: [(&str, ContentKind); 2] = [
+    (shijie_turkish::BASE_URL, shijie_turkish::KIND),
+    (novelfullcom_english::BASE_URL, novelfullcom_english::KIND),
+];
+
+/// Manga or novel, from the source the URL belongs to.
+pub fn content_kind(url: &str) -> Option<ContentKind> {
+    SOURCES
+        .iter()
+        .find(|(base, _)| url.starts_with(base))
+        .map(|(_, kind)| *kind)
+}
+
+pub fn is_manga_url(url: &str) -> bool {
+    content_kind(url) == Some(ContentKind::Manga)
+}
+
+pub fn is_novel_url(url: &str) -> bool {
+    content_kind(url) == Some(ContentKind::Novel)
+}
+
+pub fn is_manga_chapter_url(url: &str) -> bool {
+    is_manga_url(url) && shijie_turkish::is_chapter_url(url)
+}
+
+pub fn is_novel_chapter_url(url: &str) -> bool {
+    is_novel_url(url) && novelfullcom_english::is_chapter_url(url)
+}
+
+/// A chapter URL gives its serie, with only that chapter in it.
+pub async fn match_manga(url: String, net: NetOptions) -> anyhow::Result<impl Serie> {
+    if is_manga_chapter_url(&url) {
+        shijie_turkish::ShijieTurkish::from_chapter_url(url, net).await
+    } else if is_manga_url(&url) {
+        shijie_turkish::ShijieTurkish::new(url, net).await
     } else {
-        return Err(anyhow::Error::msg("Unsupported Source"));
+        Err(anyhow::Error::msg("Unsupported Source"))
     }
 }
 
-pub async fn match_novel(url: String, proxy: String) -> anyhow::Result<impl Novel> {
-    if url.starts_with("https://novelfull.com") {
-        return Ok(novelfullcom_english::NovelFullCom::new(url, proxy).await?);
+pub async fn match_novel(url: String, net: NetOptions) -> anyhow::Result<impl Novel> {
+    if is_novel_chapter_url(&url) {
+        novelfullcom_english::NovelFullCom::from_chapter_url(url, net).await
+    } else if is_novel_url(&url) {
+        novelfullcom_english::NovelFullCom::new(url, net).await
     } else {
-        return Err(anyhow::Error::msg("Unsupported Source"));
+        Err(anyhow::Error::msg("Unsupported Source"))
     }
-}
\ No newline at end of file
+}
+
+/// Only the chapter, without looking up its serie.
+pub async fn match_manga_chapter(
+    url: String,
+    net: NetOptions,
+) -> anyhow::Result<(Client, impl Chapter)> {
+    if is_manga_chapter_url(&url) {
+        let client = build_client(&net)?;
+        let (chapter, _) = shijie_turkish::ShijieTurkishChapter::from_url(&client, url).await?;
+        Ok((client, chapter))
+    } else {
+        Err(anyhow::Error::msg("Not a chapter URL"))
+    }
+}
+
+pub async fn match_novel_chapter(
+    url: String,
+    net: NetOptions,
+) -> anyhow::Result<(Client, impl NovelChapter)> {
+    if is_novel_chapter_url(&url) {
+        let client = build_client(&net)?;
+        let chapter = novelfullcom_english::NovelFullComChapter::from_url(&client, url).await?;
+        Ok((client, chapter))
+    } else {
+        Err(anyhow::Error::msg("Not a chapter URL"))
+    }
+}
+
+#[cfg(test)]
+mod matcher_tests {
+    use super::*;
+
+    #[test]
+    fn test_chapter_urls() {
+        assert!(is_manga_chapter_url(
+            "https://shijiescans.com/uwu-bolum-12/"
+        ));
+        assert!(is_manga_chapter_url(
+            "https://shijiescans.com/uwu-bolum-12/?style=list"
+        ));
+        assert!(!is_manga_chapter_url("https://shijiescans.com/series/uwu/"));
+        assert!(!is_manga_chapter_url("https://shijiescans.com/"));
+        assert!(!is_manga_chapter_url("https://shijiescans.com"));
+        assert!(!is_manga_chapter_url("https://shijiescans.com/series"));
+        assert!(!is_manga_chapter_url("https://shijiescans.com/?s=uwu"));
+        assert!(!is_manga_chapter_url("https://shijiescans.com/#top"));
+
+        assert!(is_novel_chapter_url(
+            "https://novelfull.com/uwu/chapter-1.html"
+        ));
+        assert!(!is_novel_chapter_url("https://novelfull.com/uwu.html"));
+    }
+
+    #[test]
+    fn test_content_kind() {
+        assert_eq!(
+            content_kind("https://shijiescans.com/series/uwu/"),
+            Some(ContentKind::Manga)
+        );
+        assert_eq!(
+            content_kind("https://novelfull.com/uwu.html"),
+            Some(ContentKind::Novel)
+        );
+        assert_eq!(content_kind("https://example.com/uwu"), None);
+    }
+}
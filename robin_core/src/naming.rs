@@ -0,0 +1,309 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail};
+
+/// Values that can be used in a name template.
+#[derive(Debug, Clone, Default)]
+pub struct NameContext {
+    pub title: String,
+    pub source: String,
+    pub lang: String,
+    pub volume: Option<f64>,
+    pub chapter: Option<f64>,
+    pub chapter_title: String,
+    pub date: String,
+    pub page: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Title,
+    Source,
+    Lang,
+    Volume,
+    Chapter,
+    ChapterTitle,
+    Date,
+    Page,
+}
+
+impl Field {
+    fn parse(name: &str) -> anyhow::Result<Field> {
+        Ok(match name {
+            "title" => Field::Title,
+            "source" => Field::Source,
+            "lang" => Field::Lang,
+            "volume" => Field::Volume,
+            "chapter" => Field::Chapter,
+            "chapter_title" => Field::ChapterTitle,
+            "date" => Field::Date,
+            "page" => Field::Page,
+            _ => bail!("unknown placeholder {{{}}}", name),
+        })
+    }
+
+    fn is_number(&self) -> bool {
+        matches!(self, Field::Volume | Field::Chapter | Field::Page)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Placeholder {
+        field: Field,
+        // Minimum digits of the integer part, padded with zeros
+        width: Option<usize>,
+        // Digits after the decimal point
+        precision: Option<usize>,
+    },
+}
+
+/// A name template like `{chapter:04.1} - {chapter_title}`.
+///
+/// Placeholders: `{title}`, `{source}`, `{lang}`, `{volume}`, `{chapter}`, `{chapter_title}`,
+/// `{date}` and `{page}`. Numbers take an optional spec: `{chapter:04.1}` pads the integer part
+/// to 4 digits and keeps 1 decimal (`0010.5`), `{page:03}` gives `007`. Use `{{` and `}}` for braces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameTemplate {
+    source: String,
+    segments: Vec<Segment>,
+}
+
+impl NameTemplate {
+    pub fn parse(template: &str) -> anyhow::Result<NameTemplate> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut inner = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => inner.push(c),
+                            None => bail!("unclosed placeholder in \"{}\"", template),
+                        }
+                    }
+
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(parse_placeholder(&inner)?);
+                }
+                '}' => bail!("unmatched `}}` in \"{}\"", template),
+                c => literal.push(c),
+            }
+        }
+
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+
+        Ok(NameTemplate {
+            source: template.to_string(),
+            segments,
+        })
+    }
+
+    /// Fills in the template. Missing values (e.g. no volume) become empty strings.
+    /// The result is not sanitized, see `path_safety`.
+    pub fn render(&self, ctx: &NameContext) -> String {
+        let mut buff = String::new();
+
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(s) => buff.push_str(s),
+                Segment::Placeholder {
+                    field,
+                    width,
+                    precision,
+                } => {
+                    let value = match field {
+                        Field::Title => ctx.title.clone(),
+                        Field::Source => ctx.source.clone(),
+                        Field::Lang => ctx.lang.clone(),
+                        Field::ChapterTitle => ctx.chapter_title.clone(),
+                        Field::Date => ctx.date.clone(),
+                        Field::Volume => format_number(ctx.volume, *width, *precision),
+                        Field::Chapter => format_number(ctx.chapter, *width, *precision),
+                        Field::Page => {
+                            format_number(ctx.page.map(|p| p as f64), *width, *precision)
+                        }
+                    };
+                    buff.push_str(&value);
+                }
+            }
+        }
+
+        buff
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+}
+
+impl FromStr for NameTemplate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        NameTemplate::parse(s)
+    }
+}
+
+impl std::fmt::Display for NameTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+fn parse_placeholder(inner: &str) -> anyhow::Result<Segment> {
+    let (name, spec) = match inner.split_once(':') {
+        Some((name, spec)) => (name.trim(), Some(spec.trim())),
+        None => (inner.trim(), None),
+    };
+
+    let field = Field::parse(name)?;
+
+    let Some(spec) = spec else {
+        return Ok(Segment::Placeholder {
+            field,
+            width: None,
+            precision: None,
+        });
+    };
+
+    if !field.is_number() {
+        bail!("{{{}}} doesn't take a format spec", name);
+    }
+
+    let bad_spec = || anyhow!("invalid format spec \"{}\" for {{{}}}", spec, name);
+
+    let (width, precision) = match spec.split_once('.') {
+        Some((w, p)) => (w, Some(p.parse::<usize>().map_err(|_| bad_spec())?)),
+        None => (spec, None),
+    };
+
+    let width = match width {
+        "" => None,
+        w => Some(w.parse::<usize>().map_err(|_| bad_spec())?),
+    };
+
+    Ok(Segment::Placeholder {
+        field,
+        width,
+        precision,
+    })
+}
+
+fn format_number(n: Option<f64>, width: Option<usize>, precision: Option<usize>) -> String {
+    let Some(n) = n else {
+        return String::new();
+    };
+
+    let formatted = match precision {
+        Some(p) => format!("{:.*}", p, n),
+        None if n.fract() == 0.0 => format!("{}", n as i64),
+        None => format!("{}", n),
+    };
+
+    let (int_part, rest) = match formatted.find('.') {
+        Some(i) => formatted.split_at(i),
+        None => (formatted.as_str(), ""),
+    };
+
+    format!("{:0>width$}{}", int_part, rest, width = width.unwrap_or(0))
+}
+
+/// Templates for every level of the output.
+#[derive(Debug, Clone)]
+pub struct Templates {
+    /// Serie folder, archive and ebook name
+    pub series: NameTemplate,
+    /// Chapter folder (manga) or chapter file (novel) name
+    pub chapter: NameTemplate,
+    /// Page file name, without the extension
+    pub page: NameTemplate,
+}
+
+impl Default for Templates {
+    fn default() -> Self {
+        Templates {
+            series: NameTemplate::parse("{title}").unwrap(),
+            chapter: NameTemplate::parse("{chapter_title}").unwrap(),
+            page: NameTemplate::parse("{page:04}").unwrap(),
+        }
+    }
+}
+
+/// Today's date as YYYY-MM-DD, the default `{date}`.
+pub fn today() -> String {
+    chrono::Local::now().format("%Y-%m-%d").to_string()
+}
+
+#[cfg(test)]
+mod naming_tests {
+    use super::*;
+
+    fn ctx() -> NameContext {
+        NameContext {
+            title: "Solo Leveling".into(),
+            source: "shijiescans.com".into(),
+            lang: "tr".into(),
+            chapter: Some(10.5),
+            chapter_title: "Bölüm 10.5".into(),
+            page: Some(7),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let t = NameTemplate::parse("[{lang}] {title} ({source})").unwrap();
+        assert_eq!(t.render(&ctx()), "[tr] Solo Leveling (shijiescans.com)");
+
+        let t = NameTemplate::parse("{chapter:04.1} - {chapter_title}").unwrap();
+        assert_eq!(t.render(&ctx()), "0010.5 - Bölüm 10.5");
+
+        let t = NameTemplate::parse("{page:04}").unwrap();
+        assert_eq!(t.render(&ctx()), "0007");
+
+        let t = NameTemplate::parse("v{volume:02}c{chapter:03}").unwrap();
+        assert_eq!(t.render(&ctx()), "vc010.5");
+
+        let t = NameTemplate::parse("{{{title}}}").unwrap();
+        assert_eq!(t.render(&ctx()), "{Solo Leveling}");
+    }
+
+    #[test]
+    fn test_whole_numbers() {
+        let mut c = ctx();
+        c.chapter = Some(3.0);
+
+        let t = NameTemplate::parse("{chapter:03}").unwrap();
+        assert_eq!(t.render(&c), "003");
+
+        let t = NameTemplate::parse("{chapter:03.1}").unwrap();
+        assert_eq!(t.render(&c), "003.0");
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        assert!(NameTemplate::parse("{nope}").is_err());
+        assert!(NameTemplate::parse("{title").is_err());
+        assert!(NameTemplate::parse("title}").is_err());
+        assert!(NameTemplate::parse("{title:04}").is_err());
+        assert!(NameTemplate::parse("{page:x}").is_err());
+    }
+}
@@ -0,0 +1,111 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::{Client, ClientBuilder};
+pub use reqwest_cookie_store::CookieStoreMutex;
+use tokio::sync::Mutex;
+use tokio::time::{sleep_until, Instant};
+
+/// How the HTTP client of a source is set up.
+#[derive(Debug, Clone, Default)]
+pub struct NetOptions {
+    /// Proxy URL, none when empty.
+    pub proxy: String,
+    /// Sent instead of the reqwest default when set.
+    pub user_agent: Option<String>,
+    /// Cookie jar kept between runs, a fresh one per client when `None`.
+    pub cookies: Option<Arc<CookieStoreMutex>>,
+}
+
+/// HTTP client shared by the sources.
+pub fn build_client(net: &NetOptions) -> anyhow::Result<Client> {
+    let mut client = ClientBuilder::new()
+        .connection_verbose(true)
+        .deflate(true)
+        .gzip(true)
+        .brotli(true);
+
+    if !net.proxy.is_empty() {
+        client = client.proxy(reqwest::Proxy::all(&net.proxy)?);
+    }
+    if let Some(user_agent) = &net.user_agent {
+        client = client.user_agent(user_agent);
+    }
+    client = match &net.cookies {
+        Some(jar) => client.cookie_provider(jar.clone()),
+        None => client.cookie_store(true),
+    };
+
+    Ok(client.build()?)
+}
+
+/// Slowest rate robin takes, one request a day. Slower ones would overflow the interval.
+pub const MIN_RATE: f64 = 1.0 / 86_400.0;
+
+/// Checks a rate given by the user, 0 (or less) means no limit.
+pub fn check_rate(requests: f64) -> anyhow::Result<f64> {
+    if requests.is_nan() || (requests > 0.0 && requests < MIN_RATE) {
+        anyhow::bail!(
+            "rate limit {} is out of range, use 0 for none or at least one request a day ({:.6})",
+            requests,
+            MIN_RATE
+        );
+    }
+
+    Ok(requests)
+}
+
+/// Spaces requests out evenly. Clones share the same schedule, so one limit
+/// covers every chapter downloaded at the same time.
+#[derive(Debug, Clone)]
+pub struct RateLimit {
+    interval: Duration,
+    next: Arc<Mutex<Instant>>,
+}
+
+impl RateLimit {
+    /// `requests` has to be positive and pass `check_rate`.
+    pub fn per_second(requests: f64) -> RateLimit {
+        RateLimit {
+            interval: Duration::from_secs_f64(1.0 / requests),
+            next: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    /// Waits for the next free slot.
+    pub async fn wait(&self) {
+        let mut next = self.next.lock().await;
+        sleep_until(*next).await;
+        *next = Instant::now().max(*next) + self.interval;
+    }
+}
+
+#[cfg(test)]
+mod net_tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rate_limit() {
+        let limit = RateLimit::per_second(20.0);
+        let start = Instant::now();
+
+        let waits = (0..4).map(|_| {
+            let limit = limit.clone();
+            async move { limit.wait().await }
+        });
+        futures::future::join_all(waits).await;
+
+        // The first request goes out right away
+        assert!(start.elapsed() >= Duration::from_millis(150));
+        assert!(start.elapsed() < Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn test_check_rate() {
+        assert!(check_rate(0.5).is_ok());
+        assert!(check_rate(0.0).is_ok());
+        assert!(check_rate(1e-300).is_err());
+        assert!(check_rate(f64::NAN).is_err());
+        RateLimit::per_second(MIN_RATE);
+    }
+}
@@ -0,0 +1,213 @@
+use std::collections::HashSet;
+use std::fmt::Display;
+
+use unicode_normalization::UnicodeNormalization;
+
+/// Maximum length of a sanitized name in bytes. Most filesystems allow 255,
+/// the rest is left for extensions and collision suffixes.
+pub const MAX_NAME_BYTES: usize = 200;
+
+const RESERVED_CHARS: &[char] = &['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+
+// Windows doesn't allow these names, even with an extension (e.g. "CON.txt")
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+fn trim_name(name: &str) -> &str {
+    name.trim_matches(|c: char| c.is_whitespace())
+        .trim_end_matches(['.', ' '])
+}
+
+/// Makes a single path component safe for every filesystem robin runs on.
+///
+/// Normalizes Unicode to NFC, replaces reserved and control characters with `_`,
+/// collapses whitespace, strips trailing dots/spaces, avoids hidden and Windows reserved
+/// names and caps the length to `MAX_NAME_BYTES`.
+pub fn sanitize(name: &str) -> String {
+    let replaced = name
+        .nfc()
+        .map(|c| match c {
+            c if RESERVED_CHARS.contains(&c) || c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>();
+
+    let mut name = replaced.split_whitespace().collect::<Vec<&str>>().join(" ");
+
+    if name.len() > MAX_NAME_BYTES {
+        let mut end = MAX_NAME_BYTES;
+        while !name.is_char_boundary(end) {
+            end -= 1;
+        }
+        name.truncate(end);
+    }
+
+    let mut name = trim_name(&name).to_string();
+
+    // Would be a hidden file, and skipped as a leftover
+    if name.starts_with('.') {
+        name.replace_range(..1, "_");
+    }
+
+    let stem = name.split('.').next().unwrap_or_default();
+    if RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(stem)) {
+        name.insert(0, '_');
+    }
+
+    if name.is_empty() {
+        name.push('_');
+    }
+
+    name
+}
+
+/// Hands out unique names, comparing them case-insensitively (like Windows and macOS do).
+#[derive(Debug, Default)]
+pub struct UniqueNames {
+    used: HashSet<String>,
+}
+
+impl UniqueNames {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sanitizes `name` and makes it unique. On a collision `tag` (chapter number or index)
+    /// is appended, then a counter. The result only depends on the order of the calls,
+    /// see `chapter_names` for chapters.
+    pub fn claim(&mut self, name: &str, tag: impl Display) -> String {
+        let name = sanitize(name);
+
+        let mut candidate = name.clone();
+        let mut counter = 1;
+
+        while !self.used.insert(candidate.to_lowercase()) {
+            candidate = match counter {
+                1 => format!("{} ({})", name, tag),
+                n => format!("{} ({}) {}", name, tag, n),
+            };
+            counter += 1;
+        }
+
+        candidate
+    }
+}
+
+/// Unique names for chapters given as `(name, ordinal)`, the ordinal being the position on
+/// the source page. Names are claimed in ordinal order and tagged with the ordinal, so a
+/// chapter gets the same name however the chapters are sorted. Returned in the order given.
+pub fn chapter_names(chapters: Vec<(String, usize)>) -> Vec<String> {
+    let mut order = (0..chapters.len()).collect::<Vec<usize>>();
+    order.sort_by_key(|i| chapters[*i].1);
+
+    let mut names = UniqueNames::new();
+    let mut result = vec![String::new(); chapters.len()];
+    for i in order {
+        let (name, ordinal) = &chapters[i];
+        result[i] = names.claim(name, ordinal);
+    }
+
+    result
+}
+
+/// Unique names for the `selected` chapters, claimed among every `listed` chapter so a
+/// filtered run names them like a full one. Both are `(url, name, ordinal)`. Selected
+/// chapters missing from the list (found from their own page) come after the listed ones.
+pub fn selected_chapter_names(
+    listed: &[(String, String, usize)],
+    selected: &[(String, String, usize)],
+) -> Vec<String> {
+    let mut all = listed.to_vec();
+    let mut next = listed.iter().map(|c| c.2).max().unwrap_or(0) + 1;
+    for (url, name, _) in selected {
+        if !all.iter().any(|c| c.0 == *url) {
+            all.push((url.clone(), name.clone(), next));
+            next += 1;
+        }
+    }
+
+    let names = chapter_names(all.iter().map(|c| (c.1.clone(), c.2)).collect());
+    selected
+        .iter()
+        .map(|(url, _, _)| {
+            let i = all.iter().position(|c| c.0 == *url).unwrap();
+            names[i].clone()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod path_safety_tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize() {
+        assert_eq!(sanitize("Bölüm 12: Son?"), "Bölüm 12_ Son_");
+        assert_eq!(sanitize("a/b\\c"), "a_b_c");
+        assert_eq!(sanitize("  Chapter  1...  "), "Chapter 1");
+        assert_eq!(sanitize("con"), "_con");
+        assert_eq!(sanitize("NUL.txt"), "_NUL.txt");
+        assert_eq!(sanitize("..."), "_");
+        assert_eq!(sanitize(".hack//Sign"), "_hack__Sign");
+        assert_eq!(sanitize("line\nbreak"), "line_break");
+    }
+
+    #[test]
+    fn test_sanitize_normalizes_and_caps_length() {
+        // "o" + combining diaeresis becomes "ö"
+        assert_eq!(sanitize("Bo\u{308}lu\u{308}m"), "Bölüm");
+
+        let long = "ğ".repeat(300);
+        let s = sanitize(&long);
+        assert!(s.len() <= MAX_NAME_BYTES);
+        assert!(s.chars().all(|c| c == 'ğ'));
+    }
+
+    #[test]
+    fn test_unique_names() {
+        let mut names = UniqueNames::new();
+
+        assert_eq!(names.claim("Chapter 1", 1), "Chapter 1");
+        assert_eq!(names.claim("chapter 1", 2), "chapter 1 (2)");
+        assert_eq!(names.claim("Chapter 1", 2), "Chapter 1 (2) 2");
+        assert_eq!(names.claim("Chapter: 2", 3), "Chapter_ 2");
+    }
+
+    #[test]
+    fn test_chapter_names_ignore_sorting() {
+        let chapters = vec![
+            ("Bölüm 5".to_string(), 7),
+            ("Bölüm 5".to_string(), 6),
+            ("Bölüm 6".to_string(), 8),
+        ];
+        let mut reversed = chapters.clone();
+        reversed.reverse();
+
+        let names = chapter_names(chapters);
+        assert_eq!(names, ["Bölüm 5 (7)", "Bölüm 5", "Bölüm 6"]);
+
+        let mut reversed_names = chapter_names(reversed);
+        reversed_names.reverse();
+        assert_eq!(reversed_names, names);
+    }
+
+    #[test]
+    fn test_selected_chapter_names() {
+        let listed = [
+            ("a", "Bölüm 5", 6),
+            ("b", "Bölüm 5", 7),
+            ("c", "Bölüm 6", 8),
+        ]
+        .map(|(url, name, ordinal)| (url.to_string(), name.to_string(), ordinal));
+        let full = selected_chapter_names(&listed, &listed);
+        assert_eq!(full, ["Bölüm 5", "Bölüm 5 (7)", "Bölüm 6"]);
+
+        // Only the second one selected, it keeps its full-run name
+        assert_eq!(selected_chapter_names(&listed, &listed[1..2]), full[1..2]);
+
+        let missing = [("d".to_string(), "Bölüm 5".to_string(), 1)];
+        assert_eq!(selected_chapter_names(&listed, &missing), ["Bölüm 5 (9)"]);
+    }
+}
@@ -0,0 +1,215 @@
+use std::io::Cursor;
+
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageError, ImageFormat};
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+
+/// Formats that pages can be converted to for readers without WebP/AVIF support.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TargetFormat {
+    Jpeg,
+    Png,
+}
+
+impl TargetFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            TargetFormat::Jpeg => "jpg",
+            TargetFormat::Png => "png",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ImageOptions {
+    /// Convert WebP/AVIF pages to this format. Other formats are left as they are.
+    pub convert_to: Option<TargetFormat>,
+    /// Downscale pages wider than this (keeps the aspect ratio).
+    pub max_width: Option<u32>,
+    /// Downscale pages taller than this (keeps the aspect ratio).
+    pub max_height: Option<u32>,
+    /// Convert pages to grayscale (useful for e-ink readers).
+    pub grayscale: bool,
+    /// Re-encode JPEG pages with this quality (1-100).
+    pub jpeg_quality: Option<u8>,
+}
+
+impl ImageOptions {
+    /// Returns true if processing with these options would never touch a page.
+    pub fn is_noop(&self) -> bool {
+        self.convert_to.is_none()
+            && self.max_width.is_none()
+            && self.max_height.is_none()
+            && !self.grayscale
+            && self.jpeg_quality.is_none()
+    }
+}
+
+/// Processes a single page and returns its (possibly changed) extension and bytes.
+///
+/// This is CPU heavy, use `process_page_async` from async code.
+pub fn process_page(
+    ext: &str,
+    bytes: Vec<u8>,
+    opts: &ImageOptions,
+) -> anyhow::Result<(String, Vec<u8>)> {
+    if opts.is_noop() {
+        return Ok((ext.to_string(), bytes));
+    }
+
+    let input_format = image::guess_format(&bytes)?;
+
+    let output_format = match input_format {
+        ImageFormat::WebP | ImageFormat::Avif => match opts.convert_to {
+            Some(TargetFormat::Jpeg) => ImageFormat::Jpeg,
+            Some(TargetFormat::Png) => ImageFormat::Png,
+            None => input_format,
+        },
+        other => other,
+    };
+
+    // Formats robin was built without (e.g. AVIF without the `avif` feature) stay as they are
+    let img = match image::load_from_memory_with_format(&bytes, input_format) {
+        Err(ImageError::Unsupported(_)) => return Ok((ext.to_string(), bytes)),
+        img => img?,
+    };
+
+    let max_width = opts.max_width.unwrap_or(u32::MAX);
+    let max_height = opts.max_height.unwrap_or(u32::MAX);
+    let needs_resize = img.width() > max_width || img.height() > max_height;
+    let needs_reencode = output_format == ImageFormat::Jpeg && opts.jpeg_quality.is_some();
+
+    if output_format == input_format && !needs_resize && !opts.grayscale && !needs_reencode {
+        return Ok((ext.to_string(), bytes));
+    }
+
+    let mut img = img;
+
+    if needs_resize {
+        img = img.resize(max_width, max_height, FilterType::Lanczos3);
+    }
+
+    if opts.grayscale {
+        img = img.grayscale();
+    }
+
+    let mut buffer = Cursor::new(Vec::new());
+
+    let new_ext = match output_format {
+        ImageFormat::Jpeg => {
+            // JPEG has no alpha channel
+            let img = match img.color().has_color() {
+                true => DynamicImage::ImageRgb8(img.to_rgb8()),
+                false => DynamicImage::ImageLuma8(img.to_luma8()),
+            };
+            let encoder =
+                JpegEncoder::new_with_quality(&mut buffer, opts.jpeg_quality.unwrap_or(90));
+            img.write_with_encoder(encoder)?;
+            TargetFormat::Jpeg.extension().to_string()
+        }
+        ImageFormat::Png => {
+            img.write_to(&mut buffer, ImageFormat::Png)?;
+            TargetFormat::Png.extension().to_string()
+        }
+        other => match img.write_to(&mut buffer, other) {
+            Err(ImageError::Unsupported(_)) => return Ok((ext.to_string(), bytes)),
+            result => {
+                result?;
+                ext.to_string()
+            }
+        },
+    };
+
+    Ok((new_ext, buffer.into_inner()))
+}
+
+/// Runs a CPU heavy job on the rayon thread pool so the async downloader is not blocked.
+pub async fn run_on_pool<T, F>(job: F) -> anyhow::Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> anyhow::Result<T> + Send + 'static,
+{
+    let (tx, rx) = oneshot::channel();
+
+    rayon::spawn(move || {
+        let _ = tx.send(job());
+    });
+
+    rx.await?
+}
+
+/// Runs `process_page` on the rayon thread pool.
+pub async fn process_page_async(
+    ext: String,
+    bytes: Vec<u8>,
+    opts: ImageOptions,
+) -> anyhow::Result<(String, Vec<u8>)> {
+    run_on_pool(move || process_page(&ext, bytes, &opts)).await
+}
+
+#[cfg(test)]
+mod processing_tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    fn sample_png(width: u32, height: u32) -> Vec<u8> {
+        let img = RgbImage::from_pixel(width, height, Rgb([200, 30, 30]));
+        let mut buffer = Cursor::new(Vec::new());
+        DynamicImage::ImageRgb8(img)
+            .write_to(&mut buffer, ImageFormat::Png)
+            .unwrap();
+        buffer.into_inner()
+    }
+
+    #[test]
+    fn test_noop_keeps_bytes() {
+        let bytes = sample_png(10, 10);
+        let (ext, out) = process_page("png", bytes.clone(), &ImageOptions::default()).unwrap();
+        assert_eq!(ext, "png");
+        assert_eq!(out, bytes);
+    }
+
+    #[test]
+    fn test_downscale_and_grayscale() {
+        let opts = ImageOptions {
+            max_width: Some(50),
+            grayscale: true,
+            ..Default::default()
+        };
+        let (ext, out) = process_page("png", sample_png(100, 400), &opts).unwrap();
+        let img = image::load_from_memory(&out).unwrap();
+
+        assert_eq!(ext, "png");
+        assert_eq!((img.width(), img.height()), (50, 200));
+        assert!(!img.color().has_color());
+    }
+
+    #[test]
+    fn test_small_page_not_resized() {
+        let opts = ImageOptions {
+            max_width: Some(500),
+            ..Default::default()
+        };
+        let bytes = sample_png(100, 100);
+        let (_, out) = process_page("png", bytes.clone(), &opts).unwrap();
+        assert_eq!(out, bytes);
+    }
+
+    #[test]
+    #[cfg(not(feature = "avif"))]
+    fn test_unsupported_format_keeps_bytes() {
+        let mut bytes = b"\0\0\0\x1cftypavif".to_vec();
+        bytes.resize(64, 0);
+        let opts = ImageOptions {
+            grayscale: true,
+            convert_to: Some(TargetFormat::Jpeg),
+            ..Default::default()
+        };
+        let (ext, out) = process_page("avif", bytes.clone(), &opts).unwrap();
+        assert_eq!(ext, "avif");
+        assert_eq!(out, bytes);
+    }
+}
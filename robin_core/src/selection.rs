@@ -0,0 +1,316 @@
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail};
+use chrono::NaiveDate;
+
+use crate::chapter_number::ChapterNumber;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Range {
+    from: Option<f64>,
+    to: Option<f64>,
+}
+
+impl Range {
+    fn contains(&self, n: f64) -> bool {
+        !self.from.is_some_and(|from| n < from) && !self.to.is_some_and(|to| n > to)
+    }
+}
+
+/// Which chapters to download, parsed from a comma separated list of terms:
+///
+/// - `10` a single chapter, `10:20` a range (both included), `100:` and `:20` open ranges
+/// - `!13` or `!10:12` leave chapters out
+/// - `first:10` and `latest:5` the first or last chapters by number
+/// - `whole` skip extras like `10.5`
+///
+/// An empty selection (or `all`) selects everything.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChapterSelection {
+    include: Vec<Range>,
+    exclude: Vec<Range>,
+    first: Option<usize>,
+    latest: Option<usize>,
+    whole_only: bool,
+    source: String,
+}
+
+fn parse_number(s: &str, term: &str) -> anyhow::Result<f64> {
+    s.trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|n| n.is_finite())
+        .ok_or_else(|| anyhow!("\"{}\" in \"{}\" is not a chapter number", s.trim(), term))
+}
+
+fn parse_range(s: &str, term: &str) -> anyhow::Result<Range> {
+    let Some((from, to)) = s.split_once(':') else {
+        let n = parse_number(s, term)?;
+        return Ok(Range {
+            from: Some(n),
+            to: Some(n),
+        });
+    };
+
+    let bound = |b: &str| match b.trim() {
+        "" => Ok(None),
+        b => parse_number(b, term).map(Some),
+    };
+    let range = Range {
+        from: bound(from)?,
+        to: bound(to)?,
+    };
+
+    match (range.from, range.to) {
+        (None, None) => bail!("\"{}\" needs at least one end", term),
+        (Some(from), Some(to)) if from > to => {
+            bail!("\"{}\" starts after it ends", term)
+        }
+        _ => Ok(range),
+    }
+}
+
+fn parse_count(s: &str, term: &str) -> anyhow::Result<usize> {
+    match s.trim().parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => bail!("\"{}\" needs a chapter count above 0", term),
+    }
+}
+
+impl ChapterSelection {
+    pub fn parse(s: &str) -> anyhow::Result<ChapterSelection> {
+        let mut selection = ChapterSelection {
+            source: s.to_string(),
+            ..Default::default()
+        };
+
+        for term in s.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+            if term == "all" {
+                continue;
+            } else if term == "whole" {
+                selection.whole_only = true;
+            } else if let Some(rest) = term.strip_prefix('!') {
+                selection.exclude.push(parse_range(rest, term)?);
+            } else if let Some(rest) = term.strip_prefix("first:") {
+                selection.first = Some(parse_count(rest, term)?);
+            } else if let Some(rest) = term.strip_prefix("latest:") {
+                selection.latest = Some(parse_count(rest, term)?);
+            } else {
+                selection.include.push(parse_range(term, term)?);
+            }
+        }
+
+        Ok(selection)
+    }
+
+    /// Keeps the selected chapters. `number` gives the chapter number, chapters without one
+    /// are only kept when no chapters are picked by number.
+    pub fn apply<T>(&self, chapters: &mut Vec<T>, number: impl Fn(&T) -> Option<f64>) {
+        let numbers = chapters.iter().map(number).collect::<Vec<Option<f64>>>();
+
+        // Chapters that are not excluded
+        let allowed = numbers
+            .iter()
+            .map(|n| match n {
+                Some(n) => {
+                    let extra = self.whole_only && n.fract() != 0.0;
+                    !(extra || self.exclude.iter().any(|r| r.contains(*n)))
+                }
+                None => !self.whole_only,
+            })
+            .collect::<Vec<bool>>();
+
+        let picks_chapters =
+            !self.include.is_empty() || self.first.is_some() || self.latest.is_some();
+
+        let mut keep = match picks_chapters {
+            false => allowed.clone(),
+            true => numbers
+                .iter()
+                .zip(&allowed)
+                .map(|(n, allowed)| {
+                    *allowed && n.is_some_and(|n| self.include.iter().any(|r| r.contains(n)))
+                })
+                .collect(),
+        };
+
+        // Sources list chapters in any order, first and latest go by number
+        let mut by_number = (0..numbers.len())
+            .filter(|&i| allowed[i] && numbers[i].is_some())
+            .collect::<Vec<usize>>();
+        by_number.sort_by(|&a, &b| numbers[a].unwrap().total_cmp(&numbers[b].unwrap()));
+
+        if let Some(n) = self.first {
+            for &i in by_number.iter().take(n) {
+                keep[i] = true;
+            }
+        }
+        if let Some(n) = self.latest {
+            for &i in by_number.iter().rev().take(n) {
+                keep[i] = true;
+            }
+        }
+
+        let mut keep = keep.into_iter();
+        chapters.retain(|_| keep.next().unwrap_or(false));
+    }
+}
+
+impl FromStr for ChapterSelection {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ChapterSelection::parse(s)
+    }
+}
+
+impl std::fmt::Display for ChapterSelection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+/// Order chapters are downloaded in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChapterOrder {
+    /// As listed by the source
+    #[default]
+    Source,
+    Number,
+    /// Release date, chapters of the same day by number
+    Date,
+}
+
+impl ChapterOrder {
+    /// Sorts oldest first. The sort is stable, chapters without a date keep their source
+    /// order at the end.
+    pub fn sort<T>(
+        &self,
+        chapters: &mut [T],
+        number: impl Fn(&T) -> ChapterNumber,
+        date: impl Fn(&T) -> Option<NaiveDate>,
+    ) {
+        let by_number = |a: &T, b: &T| number(a).cmp_reading(&number(b));
+
+        match self {
+            ChapterOrder::Source => {}
+            ChapterOrder::Number => chapters.sort_by(by_number),
+            ChapterOrder::Date => chapters.sort_by(|a, b| {
+                let (a_date, b_date) = (date(a), date(b));
+                match (a_date, b_date) {
+                    (Some(a_date), Some(b_date)) => a_date.cmp(&b_date),
+                    (Some(_), None) => Ordering::Less,
+                    (None, Some(_)) => Ordering::Greater,
+                    (None, None) => Ordering::Equal,
+                }
+                .then_with(|| by_number(a, b))
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod selection_tests {
+    use super::*;
+
+    fn select(selection: &str) -> Vec<f64> {
+        let mut chapters = vec![12.0, 11.0, 10.5, 10.0, 3.0, 2.0, 1.0];
+        ChapterSelection::parse(selection)
+            .unwrap()
+            .apply(&mut chapters, |n| Some(*n));
+        chapters
+    }
+
+    #[test]
+    fn test_ranges() {
+        assert_eq!(select("").len(), 7);
+        assert_eq!(select("all").len(), 7);
+        assert_eq!(select("10"), [10.0]);
+        assert_eq!(select("1:3"), [3.0, 2.0, 1.0]);
+        assert_eq!(select("11:"), [12.0, 11.0]);
+        assert_eq!(select(":2"), [2.0, 1.0]);
+        assert_eq!(select("1, 10:11"), [11.0, 10.5, 10.0, 1.0]);
+    }
+
+    #[test]
+    fn test_exclusions_and_whole() {
+        assert_eq!(select("!10:11,!2"), [12.0, 3.0, 1.0]);
+        assert_eq!(select("10:,!12"), [11.0, 10.5, 10.0]);
+        assert_eq!(select("whole,10:11"), [11.0, 10.0]);
+    }
+
+    #[test]
+    fn test_first_and_latest() {
+        assert_eq!(select("latest:2"), [12.0, 11.0]);
+        assert_eq!(select("first:2"), [2.0, 1.0]);
+        assert_eq!(select("latest:3,whole"), [12.0, 11.0, 10.0]);
+        assert_eq!(select("first:1,12"), [12.0, 1.0]);
+    }
+
+    #[test]
+    fn test_chapters_without_number() {
+        let mut chapters = vec![Some(2.0), None, Some(1.0)];
+        ChapterSelection::parse("")
+            .unwrap()
+            .apply(&mut chapters, |n| *n);
+        assert_eq!(chapters.len(), 3);
+
+        ChapterSelection::parse("1:")
+            .unwrap()
+            .apply(&mut chapters, |n| *n);
+        assert_eq!(chapters, [Some(2.0), Some(1.0)]);
+    }
+
+    #[test]
+    fn test_bad_input() {
+        for bad in [
+            "abc", "10:abc", ":", "20:10", "!", "latest:", "first:0", "latest:x", "1..2", "inf",
+        ] {
+            assert!(ChapterSelection::parse(bad).is_err(), "{}", bad);
+        }
+
+        let err = ChapterSelection::parse("1,20:10").unwrap_err().to_string();
+        assert_eq!(err, "\"20:10\" starts after it ends");
+    }
+
+    #[test]
+    fn test_order() {
+        let date = |d: u32| NaiveDate::from_ymd_opt(2024, 3, d);
+        // (name, release date) as listed by the source, newest first
+        let listed = [
+            ("Chapter 3", date(20)),
+            ("Chapter 2.5", date(10)),
+            ("Side Story", None),
+            ("Chapter 2", date(10)),
+            ("Chapter 1", None),
+        ];
+        let count = listed.len();
+
+        let sorted = |order: ChapterOrder| {
+            let mut chapters = listed
+                .iter()
+                .enumerate()
+                .map(|(i, (name, date))| (ChapterNumber::parse(name, count - i), *date))
+                .collect::<Vec<_>>();
+            order.sort(&mut chapters, |c| c.0, |c| c.1);
+            chapters
+                .into_iter()
+                .map(|c| c.0.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            sorted(ChapterOrder::Source),
+            ["Ch. 3", "Ch. 2.5", "Side Story", "Ch. 2", "Ch. 1"]
+        );
+        assert_eq!(
+            sorted(ChapterOrder::Number),
+            ["Ch. 1", "Ch. 2", "Ch. 2.5", "Ch. 3", "Side Story"]
+        );
+        assert_eq!(
+            sorted(ChapterOrder::Date),
+            ["Ch. 2", "Ch. 2.5", "Ch. 3", "Ch. 1", "Side Story"]
+        );
+    }
+}
@@ -1,94 +1,196 @@
 use std::future::Future;
+use std::path::{Path, PathBuf};
 
+use chrono::NaiveDate;
 use reqwest::Client;
-use tempfile::TempDir;
+use serde::Deserialize;
+
+use crate::chapter_check::{check_chapters, ChapterReport};
+use crate::chapter_number::ChapterNumber;
+use crate::dates::in_range;
+use crate::naming::{NameContext, Templates};
+use crate::net::{NetOptions, RateLimit};
+use crate::processing::ImageOptions;
+use crate::selection::{ChapterOrder, ChapterSelection};
+use crate::stitch::StitchMode;
 
 pub mod shijie_turkish;
 pub mod novelfullcom_english;
 
+/// What a source hosts, every source module declares it as `KIND`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentKind {
+    Manga,
+    Novel,
+}
+
+/// Options that change how a serie is downloaded.
+#[derive(Debug, Clone)]
+pub struct DownloadOptions {
+    /// How many times a page that fails validation is downloaded again.
+    pub page_retries: usize,
+    /// Image post-processing applied to every page before it is written.
+    pub image: Option<ImageOptions>,
+    /// Stitch webtoon strips of a chapter together before post-processing.
+    pub stitch: Option<StitchMode>,
+    /// How chapter folders/files and pages are named.
+    pub templates: Templates,
+    /// Where the staging folder goes, inside the destination if `None`.
+    pub temp_dir: Option<PathBuf>,
+    /// Shared limit for page (and novel chapter) requests.
+    pub rate_limit: Option<RateLimit>,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        DownloadOptions {
+            page_retries: 3,
+            image: None,
+            stitch: None,
+            templates: Templates::default(),
+            temp_dir: None,
+            rate_limit: None,
+        }
+    }
+}
+
 pub trait Serie {
-    fn new(url: String, proxy: String) -> impl Future<Output = anyhow::Result<impl Serie>> + Sync;
+    fn new(url: String, net: NetOptions) -> impl Future<Output = anyhow::Result<impl Serie>> + Sync;
     fn find_chapters(&mut self) -> impl Future<Output = ()>;
     fn get_cover(&self) -> impl Future<Output = anyhow::Result<(String, Vec<u8>)>>;
     // n_sim is number of chapters that will be downloaded in parallel
-    fn download(&mut self, n_sim: usize) -> impl Future<Output = anyhow::Result<TempDir>>;
-
-    fn parse_chapter_filter(&self, a: String) -> anyhow::Result<Option<(f64, f64)>> {
-        if a.is_empty() {
-            return Ok(None);
-        }
-
-        let splitted = a.split(":").collect::<Vec<&str>>();
+    // Chapters are written to `target` one by one as they finish (see `staging`)
+    fn download(
+        &mut self,
+        n_sim: usize,
+        target: &Path,
+        opts: &DownloadOptions,
+    ) -> impl Future<Output = anyhow::Result<()>>;
+
+    fn filter_chapters(&mut self, selection: &ChapterSelection) {
+        selection.apply(self.chapters(), |c| c.number().value());
+    }
 
-        let lower_bound: f64 = splitted[0].parse()?;
-        let upper_bound: f64 = splitted[1].parse()?;
+    // Keeps chapters released between `since` and `until` (both included)
+    fn filter_by_date(&mut self, since: Option<NaiveDate>, until: Option<NaiveDate>) {
+        self.chapters().retain(|c| in_range(c.release_date(), since, until));
+    }
 
-        Ok(Some((lower_bound, upper_bound)))
+    fn sort_chapters(&mut self, order: ChapterOrder) {
+        order.sort(self.chapters(), |c| c.number(), |c| c.release_date());
     }
 
-    fn filter_chapters(&mut self, filter_param: String) -> anyhow::Result<()> {
-        if let Some((lb, ub)) = self.parse_chapter_filter(filter_param)? {
-            self.chapters()
-                .retain(|c| c.chapter_num() >= lb && c.chapter_num() <= ub);
-        }
-        Ok(())
+    fn check_chapters(&mut self) -> ChapterReport {
+        let chapters = self
+            .chapters()
+            .iter()
+            .map(|c| (c.title().to_string(), c.number()))
+            .collect::<Vec<_>>();
+        check_chapters(&chapters)
     }
 
     fn chapter_count(&self) -> usize;
     fn chapters(&mut self) -> &mut Vec<impl Chapter>;
+    // Short name of the website, e.g. "shijiescans.com"
+    fn source_name(&self) -> &str;
+    // Language code of the content, e.g. "tr"
+    fn lang(&self) -> &str;
     fn info(&self) -> Vec<(&str, String)>;
     fn details(&self) -> String;
     fn format_info(&self, info: &Vec<(&str, String)>) -> String;
 }
 
 pub trait Chapter {
-    fn fetch(&mut self, c: &Client) -> impl Future<Output = anyhow::Result<()>>;
+    // Pages are streamed into `dir`, nothing is kept in memory
+    fn fetch(
+        &mut self,
+        c: &Client,
+        dir: &Path,
+        opts: &DownloadOptions,
+    ) -> impl Future<Output = anyhow::Result<()>>;
     fn search_image_urls(&mut self, c: &Client) -> impl Future<Output = anyhow::Result<()>>;
-    fn download(&mut self, c: &Client) -> impl Future<Output = anyhow::Result<()>>;
+    fn download(
+        &mut self,
+        c: &Client,
+        dir: &Path,
+        opts: &DownloadOptions,
+    ) -> impl Future<Output = anyhow::Result<()>>;
+    // Stitches, names (page template) and processes the pages downloaded to `raw`,
+    // they end up in `dir` and `raw` is removed
+    fn finish_pages(
+        &mut self,
+        raw: &Path,
+        dir: &Path,
+        ctx: &NameContext,
+        opts: &DownloadOptions,
+    ) -> impl Future<Output = anyhow::Result<()>>;
 
     fn page_count(&self) -> usize;
+    // Name of the chapter as listed by the source
+    fn title(&self) -> &str;
+    fn url(&self) -> &str;
+    // false if some pages never passed validation
+    fn is_complete(&self) -> bool;
     fn info(&self) -> Vec<(&str, String)>;
     fn format_info(&self, info: &Vec<(&str, String)>) -> String;
-    fn chapter_num(&self) -> f64;
+    fn number(&self) -> ChapterNumber;
+    // Sources that list volumes separately can override this
+    fn volume(&self) -> Option<f64> {
+        self.number().volume
+    }
+    // None if the source doesn't show dates or it couldn't be parsed
+    fn release_date(&self) -> Option<NaiveDate> {
+        None
+    }
 }
 
 
 pub trait Novel {
-    fn new(url: String, proxy: String) -> impl Future<Output = anyhow::Result<impl Novel>> + Sync;
+    fn new(url: String, net: NetOptions) -> impl Future<Output = anyhow::Result<impl Novel>> + Sync;
     fn find_chapters(&mut self) -> impl Future<Output = ()>;
     fn get_cover(&self) -> impl Future<Output = anyhow::Result<(String, Vec<u8>)>>;
     fn chapters(&mut self) -> &mut Vec<impl NovelChapter>;
-    fn download(&mut self, n_sim: usize) -> impl Future<Output = anyhow::Result<TempDir>>;
-
-    fn parse_chapter_filter(&self, a: String) -> anyhow::Result<Option<(f64, f64)>> {
-        if a.is_empty() {
-            return Ok(None);
-        }
-
-        let splitted = a.split(":").collect::<Vec<&str>>();
-
-        let lower_bound: f64 = splitted[0].parse()?;
-        let upper_bound: f64 = splitted[1].parse()?;
-
-        Ok(Some((lower_bound, upper_bound)))
+    fn download(
+        &mut self,
+        n_sim: usize,
+        target: &Path,
+        opts: &DownloadOptions,
+    ) -> impl Future<Output = anyhow::Result<()>>;
+
+    fn filter_chapters(&mut self, selection: &ChapterSelection) {
+        selection.apply(self.chapters(), |c| c.number().value());
     }
 
-    fn filter_chapters(&mut self, filter_param: String) -> anyhow::Result<()> {
-        if let Some((lb, ub)) = self.parse_chapter_filter(filter_param)? {
-            self.chapters()
-                .retain(|c| c.chapter_num() >= lb && c.chapter_num() <= ub);
-        }
-        Ok(())
+    fn check_chapters(&mut self) -> ChapterReport {
+        let chapters = self
+            .chapters()
+            .iter()
+            .map(|c| (c.title().to_string(), c.number()))
+            .collect::<Vec<_>>();
+        check_chapters(&chapters)
     }
 
+    fn source_name(&self) -> &str;
+    fn lang(&self) -> &str;
     fn info(&self) -> Vec<(&str, String)>;
     fn format_info(&self, info: &Vec<(&str, String)>) -> String;
 }
 
 pub trait NovelChapter {
     fn download(&mut self, c: &Client) -> impl Future<Output = anyhow::Result<()>>;
+    fn title(&self) -> &str;
+    fn url(&self) -> &str;
+    /// The chapter text, empty until downloaded
+    fn content(&self) -> &str;
 
     fn info(&self) -> Vec<(&str, String)>;
     fn format_info(&self, info: &Vec<(&str, String)>) -> String;
-    fn chapter_num(&self) -> f64;
+    fn number(&self) -> ChapterNumber;
+    fn volume(&self) -> Option<f64> {
+        self.number().volume
+    }
+    fn release_date(&self) -> Option<NaiveDate> {
+        None
+    }
 }
\ No newline at end of file
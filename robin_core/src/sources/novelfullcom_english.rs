@@ -1,41 +1,74 @@
-use super::{Novel, NovelChapter};
+use super::{ContentKind, DownloadOptions, Novel, NovelChapter};
 
-use crate::utils::{capitalize, create_progress_bar, INT_FLOAT_REGEX};
+use anyhow::bail;
 
-use reqwest::{Client, ClientBuilder};
+use crate::chapter_number::ChapterNumber;
+use crate::filetype::{content_type, detect_extension};
+use crate::manifest::{Manifest, ManifestEntry};
+use crate::naming::{today, NameContext};
+use crate::net::{build_client, NetOptions};
+use crate::path_safety::selected_chapter_names;
+use crate::staging::Staging;
+use crate::utils::{capitalize, create_progress_bar};
+
+use reqwest::Client;
 use scraper::{Html, Selector};
 
 use futures::StreamExt;
+use std::path::Path;
 use std::sync::{Arc, Mutex};
-use tempfile::{tempdir, TempDir};
 use tokio::fs::File;
 use tokio::io::AsyncWriteExt;
 
-use regex::Regex;
+pub const BASE_URL: &str = "https://novelfull.com";
+pub const KIND: ContentKind = ContentKind::Novel;
+
+/// Chapters are under the novel slug, `/<novel>/<chapter>.html`.
+pub fn is_chapter_url(url: &str) -> bool {
+    serie_slug(url).is_some()
+}
 
-const BASE_URL: &str = "https://novelfull.com";
+fn serie_slug(chapter_url: &str) -> Option<&str> {
+    let path = chapter_url.trim_start_matches(BASE_URL).trim_matches('/');
+    match path.split_once('/') {
+        Some((slug, chapter)) if !slug.is_empty() && !chapter.is_empty() => Some(slug),
+        _ => None,
+    }
+}
+
+fn same_url(a: &str, b: &str) -> bool {
+    a.trim_end_matches('/') == b.trim_end_matches('/')
+}
 
 pub struct NovelFullCom {
     pub url: String,
     pub client: Client,
     pub data: Html,
     pub chapters: Vec<NovelFullComChapter>,
+    /// Every chapter of the novel as `(url, title, number)`, file names are made unique
+    /// among all of them whatever gets filtered out
+    pub listed: Vec<(String, String, ChapterNumber)>,
+    /// Set when the novel was found from a chapter URL, only that chapter is kept
+    pub chapter_url: Option<String>,
 }
 
-impl Novel for NovelFullCom {
-    async fn new(url: String, proxy: String) -> anyhow::Result<NovelFullCom> {
-        let mut client = ClientBuilder::new()
-            .connection_verbose(true)
-            .cookie_store(true)
-            .deflate(true)
-            .gzip(true)
-            .brotli(true);
-
-        if !proxy.is_empty() {
-            client = client.proxy(reqwest::Proxy::all(proxy)?);
-        }
+impl NovelFullCom {
+    /// The novel of a chapter, its URL is the chapter's first path segment.
+    pub async fn from_chapter_url(url: String, net: NetOptions) -> anyhow::Result<NovelFullCom> {
+        let Some(slug) = serie_slug(&url) else {
+            bail!("not a chapter URL: {}", url);
+        };
+
+        let mut novel = NovelFullCom::new(format!("{}/{}.html", BASE_URL, slug), net).await?;
+        novel.chapter_url = Some(url);
 
-        let client = client.build()?;
+        Ok(novel)
+    }
+}
+
+impl Novel for NovelFullCom {
+    async fn new(url: String, net: NetOptions) -> anyhow::Result<NovelFullCom> {
+        let client = build_client(&net)?;
 
         let page = client.get(&url).send().await?.text().await?;
         let data = Html::parse_document(&page);
@@ -45,6 +78,8 @@ impl Novel for NovelFullCom {
             client,
             data,
             chapters: Vec::new(),
+            listed: Vec::new(),
+            chapter_url: None,
         })
     }
 
@@ -113,6 +148,7 @@ impl Novel for NovelFullCom {
                 self.chapters.push(NovelFullComChapter {
                     title,
                     url: ch_url,
+                    number: ChapterNumber::default(),
                     content: String::new(),
                 });
 
@@ -124,8 +160,29 @@ impl Novel for NovelFullCom {
             }
         }
 
+        for (i, c) in self.chapters.iter_mut().enumerate() {
+            c.number = ChapterNumber::parse(&c.title, i + 1);
+        }
+        self.listed = self
+            .chapters
+            .iter()
+            .map(|c| (c.url.clone(), c.title.clone(), c.number))
+            .collect();
+
         pb.finish();
         println!("\n");
+
+        if let Some(chapter_url) = &self.chapter_url {
+            self.chapters.retain(|c| same_url(&c.url, chapter_url));
+
+            // Not in the list yet (or anymore), the chapter page is enough
+            if self.chapters.is_empty() {
+                match NovelFullComChapter::from_url(&self.client, chapter_url.clone()).await {
+                    Ok(chapter) => self.chapters.push(chapter),
+                    Err(e) => println!("Can't read the chapter page: {:#}", e),
+                }
+            }
+        }
     }
 
     async fn get_cover(&self) -> anyhow::Result<(String, Vec<u8>)> {
@@ -140,46 +197,129 @@ impl Novel for NovelFullCom {
                 .unwrap_or_default()
         );
 
-        let cover_url_ext = cover_src.split(".").last().unwrap();
         let cover_resp = self.client.get(&cover_src).send().await?;
+        let cover_content_type = content_type(&cover_resp);
         let cover_bytes = cover_resp.bytes().await?;
+        let cover_ext = detect_extension(cover_content_type.as_deref(), &cover_bytes);
 
-        Ok((cover_url_ext.into(), cover_bytes.into()))
+        Ok((cover_ext.into(), cover_bytes.into()))
     }
 
-    async fn download(&mut self, n_sim: usize) -> anyhow::Result<TempDir> {
-        let tmpdir = tempdir()?;
-        let tmp_path = tmpdir.path();
-        println!("Temporary directory created at: {:?}", &tmp_path);
+    async fn download(
+        &mut self,
+        n_sim: usize,
+        target: &Path,
+        opts: &DownloadOptions,
+    ) -> anyhow::Result<()> {
+        let staging = Staging::new(target, opts.temp_dir.as_deref())?;
+        let tmp_path = staging.path();
+        println!("Staging directory created at: {:?}", &tmp_path);
 
         let client = &self.client;
+        let chapter_stage = &staging;
         let chapter_count = self.chapters.len();
         let pbar = Arc::new(Mutex::new(create_progress_bar(
             chapter_count as u64,
             "Downloading: ",
         )));
 
-        // Download cover image and save it to the temporary directory
+        // Download cover image and save it to the destination
         let (cover_src, cover_bytes) = self.get_cover().await?;
         let cover_filename = format!("cover.{}", cover_src);
         let mut f = File::create(tmp_path.join(&cover_filename)).await?;
         f.write_all(&cover_bytes).await?;
+        chapter_stage.commit(&cover_filename).await?;
+
+        // Keep the checksums of chapters from earlier runs
+        let mut manifest = Manifest::new("novel", &self.url, opts);
+        if let Ok(old) = Manifest::load(target) {
+            manifest.files = old.files;
+        }
+        manifest.insert([ManifestEntry::from_bytes(
+            cover_filename,
+            &cover_bytes,
+            self.url.clone(),
+        )]);
+        manifest.save(target)?;
+
+        let manifest = Mutex::new(manifest);
+        let chapter_manifest = &manifest;
+
+        let serie_ctx = NameContext {
+            title: self
+                .info()
+                .into_iter()
+                .find(|inf| inf.0 == "title")
+                .unwrap()
+                .1,
+            source: self.source_name().to_string(),
+            lang: self.lang().to_string(),
+            date: today(),
+            ..Default::default()
+        };
+
+        // Chapter titles can contain anything, make them safe and unique as file names
+        let render = |url: &str, title: &str, number: &ChapterNumber| {
+            let ctx = NameContext {
+                volume: number.volume,
+                chapter: number.chapter,
+                chapter_title: title.to_string(),
+                ..serie_ctx.clone()
+            };
+            (
+                url.to_string(),
+                opts.templates.chapter.render(&ctx),
+                number.ordinal,
+            )
+        };
+        let listed = self
+            .listed
+            .iter()
+            .map(|(url, title, number)| render(url, title, number))
+            .collect::<Vec<_>>();
+        let selected = self
+            .chapters
+            .iter()
+            .map(|c| render(&c.url, &c.title, &c.number))
+            .collect::<Vec<_>>();
+        let file_names = selected_chapter_names(&listed, &selected)
+            .into_iter()
+            .map(|name| format!("{}.txt", name))
+            .collect::<Vec<String>>();
 
         // Download chapters
         let stream = futures::stream::iter(
             self.chapters
                 .iter_mut()
-                .map(|c| {
+                .zip(file_names)
+                .map(|(c, file_name)| {
                     let counter = Arc::clone(&pbar);
-                    (c, counter)
+                    (c, file_name, counter)
                 })
-                .map(|(c, counter)| async move {
-                    let ch_path = tmp_path.join(format!("{}.txt", c.title));
+                .map(|(c, file_name, counter)| async move {
+                    let ch_path = tmp_path.join(&file_name);
 
+                    if let Some(limit) = &opts.rate_limit {
+                        limit.wait().await;
+                    }
                     c.download(client).await?;
 
                     let mut f = File::create(ch_path).await?;
                     f.write_all(c.content.as_bytes()).await?;
+                    chapter_stage.commit(&file_name).await?;
+
+                    let entry = ManifestEntry {
+                        chapter: c.number.chapter,
+                        volume: c.volume(),
+                        chapter_url: Some(c.url.clone()),
+                        released: c.release_date(),
+                        ..ManifestEntry::from_bytes(file_name, c.content.as_bytes(), c.url.clone())
+                    };
+
+                    let mut manifest = chapter_manifest.lock().unwrap();
+                    manifest.insert([entry]);
+                    manifest.save(target)?;
+                    drop(manifest);
 
                     let mut count_bar = counter.lock().unwrap();
                     count_bar.inc();
@@ -197,13 +337,22 @@ impl Novel for NovelFullCom {
         }
 
         pbar.lock().unwrap().finish_print("Downloaded!");
-        Ok(tmpdir)
+        staging.finish()?;
+        Ok(())
     }
 
     fn chapters(&mut self) -> &mut Vec<impl super::NovelChapter> {
         &mut self.chapters
     }
 
+    fn source_name(&self) -> &str {
+        "novelfull.com"
+    }
+
+    fn lang(&self) -> &str {
+        "en"
+    }
+
     fn info(&self) -> Vec<(&str, String)> {
         let mut buff: Vec<(&str, String)> = Vec::new();
 
@@ -294,9 +443,41 @@ impl Novel for NovelFullCom {
 pub struct NovelFullComChapter {
     title: String,
     url: String,
+    number: ChapterNumber,
     content: String,
 }
 
+impl NovelFullComChapter {
+    /// A chapter from its own page, without its novel.
+    pub async fn from_url(c: &Client, url: String) -> anyhow::Result<NovelFullComChapter> {
+        let title_selector = Selector::parse("a.chapter-title").unwrap();
+
+        let page = c.get(&url).send().await?.text().await?;
+        let data = Html::parse_document(&page);
+
+        let title = data
+            .select(&title_selector)
+            .next()
+            .map(|t| t.text().collect::<String>())
+            .filter(|t| !t.trim().is_empty())
+            .unwrap_or_else(|| {
+                let slug = url
+                    .trim_end_matches('/')
+                    .rsplit('/')
+                    .next()
+                    .unwrap_or_default();
+                slug.trim_end_matches(".html").replace('-', " ")
+            });
+
+        Ok(NovelFullComChapter {
+            number: ChapterNumber::parse(&title, 1),
+            title,
+            url,
+            content: String::new(),
+        })
+    }
+}
+
 impl NovelChapter for NovelFullComChapter {
     async fn download(&mut self, c: &Client) -> anyhow::Result<()> {
         let page = c.get(&self.url).send().await?.text().await?;
@@ -315,13 +496,20 @@ impl NovelChapter for NovelFullComChapter {
         Ok(())
     }
 
-    fn chapter_num(&self) -> f64 {
-        let re = Regex::new(INT_FLOAT_REGEX).unwrap();
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
 
-        // find number regex in title
-        let num = re.find(&self.title).unwrap().as_str();
+    fn content(&self) -> &str {
+        &self.content
+    }
 
-        num.parse().unwrap()
+    fn number(&self) -> ChapterNumber {
+        self.number
     }
 
     fn format_info(&self, info: &Vec<(&str, String)>) -> String {
@@ -364,8 +552,14 @@ mod nvl_fll_tests {
     #[tokio::test]
     async fn test_download() {
         let url = "https://novelfull.com/everyone-wants-to-pamper-the-bigshot-researcher-after-her-rebirth.html";
-        let mut novel = NovelFullCom::new(url.into(), "".into()).await.unwrap();
+        let mut novel = NovelFullCom::new(url.into(), NetOptions::default())
+            .await
+            .unwrap();
         novel.find_chapters().await;
-        let _ = novel.download(1).await.unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        novel
+            .download(1, dir.path(), &DownloadOptions::default())
+            .await
+            .unwrap();
     }
 }
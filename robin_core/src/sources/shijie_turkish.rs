@@ -1,38 +1,81 @@
 use super::*;
-use crate::utils::{capitalize, create_progress_bar};
+use crate::chapter_number::ChapterNumber;
+use crate::comicinfo::{ComicInfo, COMIC_INFO_FILE};
+use crate::dates::{local_today, parse_date};
+use crate::filetype::{content_type, detect_extension};
+use crate::manifest::{now, Manifest, ManifestEntry};
+use crate::naming::{today, NameContext};
+use crate::net::{build_client, NetOptions};
+use crate::path_safety::{selected_chapter_names, UniqueNames};
+use crate::processing::process_page_async;
+use crate::staging::Staging;
+use crate::stitch::stitch_chapter_async;
+use crate::utils::{capitalize, create_progress_bar, sha256_hex};
+use crate::validation::{fetch_page_to_file, StoredPage};
+use anyhow::bail;
 use futures::StreamExt;
-use reqwest::{Client, ClientBuilder};
+use reqwest::{Client, Url};
 use scraper::{selectable::Selectable, Html, Selector};
+use std::path::Path;
 use std::sync::{Arc, Mutex};
 
-use tempfile::{tempdir, TempDir};
-use tokio::fs::{create_dir, File};
+use tokio::fs::{create_dir, read, remove_dir_all, remove_file, rename, File};
 use tokio::io::AsyncWriteExt;
 
 use serde::Deserialize;
 
+pub const BASE_URL: &str = "https://shijiescans.com";
+pub const KIND: ContentKind = ContentKind::Manga;
+
+// Pages are downloaded here (inside the chapter folder) before they get their final names
+const RAW_DIR: &str = ".pages";
+
+/// Chapter pages live at the site root, series under `/series/`.
+pub fn is_chapter_url(url: &str) -> bool {
+    let Ok(url) = Url::parse(url) else {
+        return false;
+    };
+
+    let first = url.path_segments().and_then(|mut s| s.next());
+    first.is_some_and(|s| !s.is_empty() && s != "series")
+}
+
+fn same_url(a: &str, b: &str) -> bool {
+    a.trim_end_matches('/') == b.trim_end_matches('/')
+}
+
 #[derive(Debug)]
 pub struct ShijieTurkish {
     pub url: String,
     pub client: Client,
     pub data: Html,
     pub chapters: Vec<ShijieTurkishChapter>,
+    /// Every chapter of the serie page as `(url, name, number)`, folder names are made
+    /// unique among all of them whatever gets filtered out
+    pub listed: Vec<(String, String, ChapterNumber)>,
+    /// Set when the serie was found from a chapter URL, only that chapter is kept
+    pub chapter_url: Option<String>,
 }
 
-impl Serie for ShijieTurkish {
-    async fn new(url: String, proxy: String) -> anyhow::Result<ShijieTurkish> {
-        let mut client = ClientBuilder::new()
-            .connection_verbose(true)
-            .cookie_store(true)
-            .deflate(true)
-            .gzip(true)
-            .brotli(true);
-
-        if !proxy.is_empty() {
-            client = client.proxy(reqwest::Proxy::all(proxy)?);
-        }
+impl ShijieTurkish {
+    /// The serie of a chapter page, linked from the chapter itself.
+    pub async fn from_chapter_url(url: String, net: NetOptions) -> anyhow::Result<ShijieTurkish> {
+        let client = build_client(&net)?;
+        let (_, serie_url) = ShijieTurkishChapter::from_url(&client, url.clone()).await?;
+        let Some(serie_url) = serie_url else {
+            bail!("can't find the serie of {}", url);
+        };
 
-        let client = client.build()?;
+        let mut serie = ShijieTurkish::new(serie_url, net).await?;
+        serie.chapter_url = Some(url);
+
+        Ok(serie)
+    }
+}
+
+impl Serie for ShijieTurkish {
+    async fn new(url: String, net: NetOptions) -> anyhow::Result<ShijieTurkish> {
+        let client = build_client(&net)?;
 
         let page = client.get(&url).send().await?;
         let data = Html::parse_document(page.text().await?.as_str());
@@ -42,6 +85,8 @@ impl Serie for ShijieTurkish {
             client,
             data,
             chapters: Vec::new(),
+            listed: Vec::new(),
+            chapter_url: None,
         })
     }
 
@@ -85,10 +130,35 @@ impl Serie for ShijieTurkish {
                 date,
                 name,
                 url,
+                number: ChapterNumber::default(),
                 page_urls: Vec::new(),
-                page_data: Vec::new(),
+                pages: Vec::new(),
+                failed_pages: Vec::new(),
             });
         }
+
+        // Listed newest first
+        let count = self.chapters.len();
+        for (i, c) in self.chapters.iter_mut().enumerate() {
+            c.number = ChapterNumber::parse(&c.name, count - i);
+        }
+        self.listed = self
+            .chapters
+            .iter()
+            .map(|c| (c.url.clone(), c.name.clone(), c.number))
+            .collect();
+
+        if let Some(chapter_url) = &self.chapter_url {
+            self.chapters.retain(|c| same_url(&c.url, chapter_url));
+
+            // Not in the list yet (or anymore), the chapter page is enough
+            if self.chapters.is_empty() {
+                match ShijieTurkishChapter::from_url(&self.client, chapter_url.clone()).await {
+                    Ok((chapter, _)) => self.chapters.push(chapter),
+                    Err(e) => println!("Can't read the chapter page: {:#}", e),
+                }
+            }
+        }
     }
 
     fn chapters(&mut self) -> &mut Vec<impl Chapter> {
@@ -104,62 +174,195 @@ impl Serie for ShijieTurkish {
             .unwrap()
             .attr("src")
             .unwrap();
-        let cover_url_ext = cover_url.split(".").last().unwrap();
 
         let cover_response = self.client.get(cover_url).send().await?;
+        let cover_content_type = content_type(&cover_response);
         let cover_bytes = cover_response.bytes().await?;
+        let cover_ext = detect_extension(cover_content_type.as_deref(), &cover_bytes);
 
-        Ok((cover_url_ext.to_string(), cover_bytes.into()))
+        Ok((cover_ext.to_string(), cover_bytes.into()))
     }
 
-    async fn download(&mut self, n_sim: usize) -> anyhow::Result<TempDir> {
-        let tmpdir = tempdir()?;
-        let tmp_path = tmpdir.path();
-        println!("Temporary directory created to: {}", &tmp_path.display());
+    async fn download(
+        &mut self,
+        n_sim: usize,
+        target: &Path,
+        opts: &DownloadOptions,
+    ) -> anyhow::Result<()> {
+        let staging = Staging::new(target, opts.temp_dir.as_deref())?;
+        let tmp_path = staging.path();
+        println!("Staging directory created at: {}", &tmp_path.display());
 
         let client = &self.client;
+        let chapter_stage = &staging;
         let chapter_count = self.chapter_count();
         let pbar = Arc::new(Mutex::new(create_progress_bar(
             chapter_count as u64,
             "Downloading: ",
         )));
 
-        // Download cover image and save it to the temporary directory
+        // Keep the checksums of chapters from earlier runs
+        let mut manifest = Manifest::new("manga", &self.url, opts);
+        if let Ok(old) = Manifest::load(target) {
+            manifest.files = old.files;
+        }
+
+        // Download cover image and save it to the destination
         let cover_data = self.get_cover().await?;
         let cover_filename = format!("cover.{}", cover_data.0);
-        let mut f = File::create(tmp_path.join(cover_filename)).await?;
+        let mut f = File::create(tmp_path.join(&cover_filename)).await?;
         f.write_all(cover_data.1.as_ref()).await?;
+        staging.commit(&cover_filename).await?;
 
         // Save details to a json file
         let details = self.details();
         let mut f = File::create(tmp_path.join("details.json")).await?;
         f.write_all(details.as_bytes()).await?;
+        staging.commit("details.json").await?;
+
+        manifest.insert([
+            ManifestEntry::from_bytes(cover_filename, &cover_data.1, self.url.clone()),
+            ManifestEntry::from_bytes("details.json".into(), details.as_bytes(), self.url.clone()),
+        ]);
+        manifest.save(target)?;
+
+        let manifest = Mutex::new(manifest);
+        let chapter_manifest = &manifest;
+
+        let info = self.info();
+        let find_info = |key: &str| info.iter().find(|inf| inf.0 == key).unwrap().1.clone();
+        let writer = find_info("author");
+        let chapter_writer = &writer;
+
+        let serie_ctx = NameContext {
+            title: find_info("title"),
+            source: self.source_name().to_string(),
+            lang: self.lang().to_string(),
+            date: today(),
+            ..Default::default()
+        };
+
+        let contexts = self
+            .chapters
+            .iter()
+            .map(|c| NameContext {
+                volume: c.volume(),
+                chapter: c.number.chapter,
+                chapter_title: c.name.clone(),
+                ..serie_ctx.clone()
+            })
+            .collect::<Vec<NameContext>>();
+
+        // Chapter names can contain anything, make them safe and unique as folder names
+        let render = |url: &str, name: &str, number: &ChapterNumber| {
+            let ctx = NameContext {
+                volume: number.volume,
+                chapter: number.chapter,
+                chapter_title: name.to_string(),
+                ..serie_ctx.clone()
+            };
+            (
+                url.to_string(),
+                opts.templates.chapter.render(&ctx),
+                number.ordinal,
+            )
+        };
+        let listed = self
+            .listed
+            .iter()
+            .map(|(url, name, number)| render(url, name, number))
+            .collect::<Vec<_>>();
+        let selected = self
+            .chapters
+            .iter()
+            .map(|c| render(&c.url, &c.name, &c.number))
+            .collect::<Vec<_>>();
+        let dir_names = selected_chapter_names(&listed, &selected)
+            .into_iter()
+            .zip(contexts)
+            .collect::<Vec<(String, NameContext)>>();
 
         // The first map is to clone the current_chapter mutex.
         // There is probably better ways to do it but I'm not sure how to do it
         let stream = futures::stream::iter(
             self.chapters
                 .iter_mut()
-                .map(|c| {
+                .zip(dir_names)
+                .map(|(c, (dir_name, ctx))| {
                     let counter = Arc::clone(&pbar);
-                    (c, counter)
+                    (c, dir_name, ctx, counter)
                 })
-                .map(|(c, counter)| async move {
-                    let dir_path = tmp_path.join(&c.name);
-                    create_dir(&dir_path).await?;
+                .map(|(c, dir_name, ctx, counter)| async move {
+                    let dir_path = tmp_path.join(&dir_name);
 
-                    c.download(client).await?;
-
-                    for page in &c.page_data {
-                        let filename = page.0.split("/").last().unwrap();
-                        let temp_page_path = dir_path.join(filename);
+                    // Leftover from a crashed run
+                    if dir_path.exists() {
+                        remove_dir_all(&dir_path).await?;
+                    }
 
-                        let mut f = File::create(temp_page_path).await?;
-                        f.write_all(page.1.as_ref()).await?;
+                    // Raw pages go to their own folder so they can't clash with templated names
+                    let raw_path = dir_path.join(RAW_DIR);
+                    create_dir(&dir_path).await?;
+                    create_dir(&raw_path).await?;
+
+                    c.download(client, &raw_path, opts).await?;
+
+                    c.finish_pages(&raw_path, &dir_path, &ctx, opts).await?;
+
+                    let comic_info = ComicInfo {
+                        series: ctx.title.clone(),
+                        title: c.name.clone(),
+                        number: ctx.chapter,
+                        volume: ctx.volume,
+                        writer: chapter_writer.clone(),
+                        web: c.url.clone(),
+                        language: ctx.lang.clone(),
+                        page_count: c.pages.len(),
+                        release_date: c.release_date(),
+                    }
+                    .to_xml();
+                    let mut f = File::create(dir_path.join(COMIC_INFO_FILE)).await?;
+                    f.write_all(comic_info.as_bytes()).await?;
+
+                    // Mark the chapter so it doesn't look complete
+                    if !c.is_complete() {
+                        let mut f = File::create(dir_path.join(".incomplete")).await?;
+                        f.write_all(c.failed_pages.join("\n").as_bytes()).await?;
                     }
 
-                    // Clear page data to save memory
-                    c.page_data.clear();
+                    chapter_stage.commit(&dir_name).await?;
+
+                    let entries = c
+                        .pages
+                        .iter()
+                        .map(|page| ManifestEntry {
+                            path: format!("{}/{}", dir_name, page.file_name),
+                            sha256: page.sha256.clone(),
+                            size: page.size,
+                            url: page.url.clone(),
+                            chapter: ctx.chapter,
+                            volume: ctx.volume,
+                            chapter_url: Some(c.url.clone()),
+                            released: c.release_date(),
+                            downloaded_at: now(),
+                        })
+                        .chain([ManifestEntry {
+                            chapter: ctx.chapter,
+                            volume: ctx.volume,
+                            chapter_url: Some(c.url.clone()),
+                            released: c.release_date(),
+                            ..ManifestEntry::from_bytes(
+                                format!("{}/{}", dir_name, COMIC_INFO_FILE),
+                                comic_info.as_bytes(),
+                                c.url.clone(),
+                            )
+                        }]);
+
+                    let mut manifest = chapter_manifest.lock().unwrap();
+                    manifest.remove_dir(&dir_name);
+                    manifest.insert(entries);
+                    manifest.save(target)?;
+                    drop(manifest);
 
                     // Notify progress
                     let mut counter = counter.lock().unwrap();
@@ -178,13 +381,39 @@ impl Serie for ShijieTurkish {
         }
 
         pbar.lock().unwrap().finish_print("Downloaded!");
-        Ok(tmpdir)
+        staging.finish()?;
+
+        let incomplete = self
+            .chapters
+            .iter()
+            .filter(|c| !c.is_complete())
+            .collect::<Vec<_>>();
+
+        if !incomplete.is_empty() {
+            println!("\n{} chapters are incomplete:", incomplete.len());
+            for c in incomplete {
+                println!("  {}", c.name);
+                for failed in &c.failed_pages {
+                    println!("    {}", failed);
+                }
+            }
+        }
+
+        Ok(())
     }
 
     fn chapter_count(&self) -> usize {
         self.chapters.len()
     }
 
+    fn source_name(&self) -> &str {
+        "shijiescans.com"
+    }
+
+    fn lang(&self) -> &str {
+        "tr"
+    }
+
     fn info(&self) -> Vec<(&str, String)> {
         // Selectors for Info
         let title_selector = Selector::parse("h1.entry-title").unwrap();
@@ -335,32 +564,76 @@ pub struct ShijieTurkishChapter {
     pub date: String,
     pub name: String,
     pub url: String,
+    pub number: ChapterNumber,
 
     page_urls: Vec<String>,
-    page_data: Vec<(String, Vec<u8>)>,
+    pages: Vec<StoredPage>,
+    failed_pages: Vec<String>,
 }
 
-impl Chapter for ShijieTurkishChapter {
-    async fn fetch(&mut self, c: &Client) -> anyhow::Result<()> {
-        let stream = futures::stream::iter(self.page_urls.iter().map(|uri| async move {
-            let p = c.get(uri).send().await;
+impl ShijieTurkishChapter {
+    /// A chapter from its own page, with the URL of its serie when the page links to it.
+    pub async fn from_url(
+        c: &Client,
+        url: String,
+    ) -> anyhow::Result<(ShijieTurkishChapter, Option<String>)> {
+        let title_selector = Selector::parse("h1.entry-title").unwrap();
+        let serie_selector = Selector::parse(".allc > a").unwrap();
+
+        let page = c.get(&url).send().await?.text().await?;
+        let data = Html::parse_document(&page);
+
+        let name: String = data
+            .select(&title_selector)
+            .next()
+            .map(|t| t.text().collect())
+            .unwrap_or_default();
+        let serie_url = data
+            .select(&serie_selector)
+            .next()
+            .and_then(|a| a.attr("href"))
+            .map(String::from);
+
+        let chapter = ShijieTurkishChapter {
+            date: String::new(),
+            number: ChapterNumber::parse(&name, 1),
+            name,
+            url,
+            page_urls: Vec::new(),
+            pages: Vec::new(),
+            failed_pages: Vec::new(),
+        };
+
+        Ok((chapter, serie_url))
+    }
+}
 
-            p
+impl Chapter for ShijieTurkishChapter {
+    async fn fetch(
+        &mut self,
+        c: &Client,
+        dir: &Path,
+        opts: &DownloadOptions,
+    ) -> anyhow::Result<()> {
+        let stream = futures::stream::iter(self.page_urls.iter().enumerate().map(|(i, uri)| {
+            fetch_page_to_file(
+                c,
+                uri,
+                dir,
+                format!("{:0>4}", i),
+                opts.page_retries,
+                opts.rate_limit.as_ref(),
+            )
         }))
         .buffered(10);
 
         let results = stream.collect::<Vec<_>>().await;
 
         for (i, res) in results.into_iter().enumerate() {
-            let response = res?;
-
-            let response_url = response.url().to_string();
-            let file_ext = response_url.split(".").last().unwrap();
-
-            self.page_data.push((
-                format!("{:0>4}.{}", i, file_ext),
-                response.bytes().await?.into(),
-            ));
+            match res {
+                Ok(page) => self.pages.push(page),
+                Err(e) => self.failed_pages.push(format!("{:0>4}: {:#}", i, e)),
+            }
         }
 
         Ok(())
@@ -394,13 +667,99 @@ impl Chapter for ShijieTurkishChapter {
         self.page_urls.len()
     }
 
-    async fn download(&mut self, c: &Client) -> anyhow::Result<()> {
+    fn title(&self) -> &str {
+        &self.name
+    }
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    async fn download(
+        &mut self,
+        c: &Client,
+        dir: &Path,
+        opts: &DownloadOptions,
+    ) -> anyhow::Result<()> {
         self.search_image_urls(c).await?;
-        self.fetch(c).await?;
+        self.fetch(c, dir, opts).await?;
+
+        Ok(())
+    }
+
+    async fn finish_pages(
+        &mut self,
+        raw: &Path,
+        dir: &Path,
+        ctx: &NameContext,
+        opts: &DownloadOptions,
+    ) -> anyhow::Result<()> {
+        if let Some(mode) = opts.stitch {
+            // Stitching needs every strip of the chapter at once
+            let mut pages = Vec::new();
+            for page in self.pages.drain(..) {
+                let path = raw.join(&page.file_name);
+                pages.push((page.file_name, read(&path).await?));
+                remove_file(&path).await?;
+            }
+
+            for (file_name, bytes) in stitch_chapter_async(pages, mode).await? {
+                let mut f = File::create(raw.join(&file_name)).await?;
+                f.write_all(&bytes).await?;
+
+                self.pages.push(StoredPage {
+                    file_name,
+                    url: self.url.clone(),
+                    sha256: sha256_hex(&bytes),
+                    size: bytes.len() as u64,
+                });
+            }
+        }
+
+        let mut page_names = UniqueNames::new();
+
+        for (i, page) in self.pages.iter_mut().enumerate() {
+            let raw_page_path = raw.join(&page.file_name);
+            let (stem, ext) = page.file_name.rsplit_once(".").unwrap();
+
+            // Page number from the original name, failed pages leave gaps
+            let page_ctx = NameContext {
+                page: Some(stem.parse().unwrap_or(i)),
+                ..ctx.clone()
+            };
+            let page_name = page_names.claim(&opts.templates.page.render(&page_ctx), i);
+
+            match &opts.image {
+                Some(image_opts) => {
+                    // One page at a time, on the rayon pool
+                    let bytes = read(&raw_page_path).await?;
+                    let (ext, bytes) =
+                        process_page_async(ext.to_string(), bytes, image_opts.clone()).await?;
+
+                    page.file_name = format!("{}.{}", page_name, ext);
+                    page.sha256 = sha256_hex(&bytes);
+                    page.size = bytes.len() as u64;
+
+                    let mut f = File::create(dir.join(&page.file_name)).await?;
+                    f.write_all(&bytes).await?;
+                    remove_file(&raw_page_path).await?;
+                }
+                None => {
+                    page.file_name = format!("{}.{}", page_name, ext);
+                    rename(&raw_page_path, dir.join(&page.file_name)).await?;
+                }
+            }
+        }
+
+        remove_dir_all(raw).await?;
 
         Ok(())
     }
 
+    fn is_complete(&self) -> bool {
+        self.failed_pages.is_empty()
+    }
+
     fn info(&self) -> Vec<(&str, String)> {
         let mut map: Vec<(&str, String)> = Vec::new();
 
@@ -408,6 +767,7 @@ impl Chapter for ShijieTurkishChapter {
         map.push(("release date", self.date.clone()));
         map.push(("source", self.url.clone()));
         map.push(("page count", format!("{}", self.page_count())));
+        map.push(("failed pages", format!("{}", self.failed_pages.len())));
 
         map
     }
@@ -425,19 +785,12 @@ impl Chapter for ShijieTurkishChapter {
         buff
     }
 
-    fn chapter_num(&self) -> f64 {
-        let num = *self
-            .name
-            .split(" ")
-            .into_iter()
-            .map(|word| word.replace(",", "."))
-            .filter_map(|s| s.parse::<f64>().ok())
-            .collect::<Vec<f64>>()
-            .first()
-            .unwrap();
+    fn number(&self) -> ChapterNumber {
+        self.number
+    }
 
-        // Round to 2 decimal points. Example: 123.45
-        (num * 100.0).round() / 100.0
+    fn release_date(&self) -> Option<NaiveDate> {
+        parse_date(&self.date, "tr", local_today())
     }
 }
 
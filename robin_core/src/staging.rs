@@ -0,0 +1,145 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Name of the staging folder created inside the destination.
+pub const PARTIAL_DIR: &str = ".robin-partial";
+
+/// Downloads go to a staging folder first and every finished chapter is moved
+/// into the destination with a rename, so a crash never leaves a half chapter
+/// that looks complete.
+#[derive(Debug)]
+pub struct Staging {
+    target: PathBuf,
+    partial: PathBuf,
+}
+
+impl Staging {
+    /// Creates the destination and the staging folder. The staging folder is inside
+    /// `target` unless `temp_dir` is given.
+    pub fn new(target: impl Into<PathBuf>, temp_dir: Option<&Path>) -> io::Result<Staging> {
+        let target = target.into();
+        fs::create_dir_all(&target)?;
+
+        let partial = match temp_dir {
+            Some(dir) => {
+                let name = target.file_name().unwrap_or_default().to_string_lossy();
+                dir.join(format!("{}{}", name, PARTIAL_DIR))
+            }
+            None => target.join(PARTIAL_DIR),
+        };
+        fs::create_dir_all(&partial)?;
+
+        Ok(Staging { target, partial })
+    }
+
+    /// Folder to write unfinished files and chapters to.
+    pub fn path(&self) -> &Path {
+        &self.partial
+    }
+
+    pub fn target(&self) -> &Path {
+        &self.target
+    }
+
+    /// Moves `name` (a file or folder in the staging folder) into the destination,
+    /// replacing an older copy if there is one.
+    pub async fn commit(&self, name: &str) -> anyhow::Result<()> {
+        let from = self.partial.join(name);
+        let to = self.target.join(name);
+        let old = self.partial.join(format!(".old-{}", name));
+
+        tokio::task::spawn_blocking(move || commit_blocking(&from, &to, &old)).await??;
+
+        Ok(())
+    }
+
+    /// Removes the staging folder.
+    pub fn finish(self) -> io::Result<()> {
+        fs::remove_dir_all(&self.partial)
+    }
+}
+
+fn commit_blocking(from: &Path, to: &Path, old: &Path) -> io::Result<()> {
+    if to.exists() {
+        fs::rename(to, old)?;
+    }
+
+    if fs::rename(from, to).is_err() {
+        // Different filesystems (--temp-dir), copy next to the destination first
+        let tmp = to.with_file_name(format!(
+            ".{}.tmp",
+            to.file_name().unwrap_or_default().to_string_lossy()
+        ));
+        copy_all(from, &tmp)?;
+        fs::rename(&tmp, to)?;
+        remove_all(from)?;
+    }
+
+    if old.exists() {
+        remove_all(old)?;
+    }
+
+    Ok(())
+}
+
+fn copy_all(from: &Path, to: &Path) -> io::Result<()> {
+    if from.is_dir() {
+        fs::create_dir_all(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_all(&entry.path(), &to.join(entry.file_name()))?;
+        }
+    } else {
+        fs::copy(from, to)?;
+    }
+    Ok(())
+}
+
+fn remove_all(path: &Path) -> io::Result<()> {
+    match path.is_dir() {
+        true => fs::remove_dir_all(path),
+        false => fs::remove_file(path),
+    }
+}
+
+#[cfg(test)]
+mod staging_tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_commit_replaces_old_chapter() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("Serie");
+
+        fs::create_dir_all(target.join("Chapter 1")).unwrap();
+        fs::write(target.join("Chapter 1").join("old.jpg"), b"old").unwrap();
+
+        let staging = Staging::new(&target, None).unwrap();
+        let chapter = staging.path().join("Chapter 1");
+        fs::create_dir(&chapter).unwrap();
+        fs::write(chapter.join("0000.jpg"), b"new").unwrap();
+
+        staging.commit("Chapter 1").await.unwrap();
+        staging.finish().unwrap();
+
+        assert!(target.join("Chapter 1").join("0000.jpg").exists());
+        assert!(!target.join("Chapter 1").join("old.jpg").exists());
+        assert!(!target.join(PARTIAL_DIR).exists());
+    }
+
+    #[tokio::test]
+    async fn test_external_temp_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let temp = tempfile::tempdir().unwrap();
+        let target = dir.path().join("Serie");
+
+        let staging = Staging::new(&target, Some(temp.path())).unwrap();
+        assert!(staging.path().starts_with(temp.path()));
+
+        fs::write(staging.path().join("cover.jpg"), b"cover").unwrap();
+        staging.commit("cover.jpg").await.unwrap();
+
+        assert_eq!(fs::read(target.join("cover.jpg")).unwrap(), b"cover");
+    }
+}
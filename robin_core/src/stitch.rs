@@ -0,0 +1,204 @@
+use std::io::Cursor;
+
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::{self, FilterType};
+use image::{ImageFormat, Rgb, RgbImage};
+use serde::{Deserialize, Serialize};
+
+use crate::processing::run_on_pool;
+
+/// Channels that differ less than this from the row's first pixel are counted as the same color.
+const GUTTER_TOLERANCE: u8 = 12;
+/// Minimum number of blank rows between two panels to count as a gutter.
+const MIN_GUTTER_HEIGHT: u32 = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StitchMode {
+    /// Re-split the stitched strip at gutters into pages around `page_height` pixels high.
+    /// If `page_height` is `None`, it is picked from the strip width (1:1.6, like a phone screen).
+    Split { page_height: Option<u32> },
+    /// Keep the whole chapter as one tall image.
+    Tall,
+}
+
+/// Stitches strips vertically into one canvas. Narrower strips are scaled up to the widest one.
+pub fn stitch(strips: &[RgbImage]) -> RgbImage {
+    let width = strips.iter().map(|s| s.width()).max().unwrap_or(0);
+    let scaled_height = |s: &RgbImage| (s.height() as u64 * width as u64 / s.width() as u64) as u32;
+    let height = strips.iter().map(scaled_height).sum();
+
+    let mut canvas = RgbImage::from_pixel(width, height, Rgb([255, 255, 255]));
+    let mut y = 0;
+
+    for strip in strips {
+        if strip.width() == width {
+            imageops::replace(&mut canvas, strip, 0, y as i64);
+        } else {
+            let resized =
+                imageops::resize(strip, width, scaled_height(strip), FilterType::Lanczos3);
+            imageops::replace(&mut canvas, &resized, 0, y as i64);
+        }
+        y += scaled_height(strip);
+    }
+
+    canvas
+}
+
+fn is_blank_row(img: &RgbImage, y: u32) -> bool {
+    let first = img.get_pixel(0, y);
+
+    (0..img.width()).all(|x| {
+        let p = img.get_pixel(x, y);
+        p.0.iter()
+            .zip(first.0.iter())
+            .all(|(a, b)| a.abs_diff(*b) <= GUTTER_TOLERANCE)
+    })
+}
+
+/// Finds the gutter between rows `from` and `to` closest to `target` and returns the row in its middle.
+fn find_gutter(img: &RgbImage, from: u32, to: u32, target: u32) -> Option<u32> {
+    let mut best: Option<u32> = None;
+    let mut run_start: Option<u32> = None;
+
+    for y in from..=to {
+        let blank = y < to && is_blank_row(img, y);
+
+        match (blank, run_start) {
+            (true, None) => run_start = Some(y),
+            (false, Some(start)) => {
+                if y - start >= MIN_GUTTER_HEIGHT {
+                    let cut = start + (y - start) / 2;
+                    let closer = match best {
+                        Some(b) => cut.abs_diff(target) < b.abs_diff(target),
+                        None => true,
+                    };
+                    if closer {
+                        best = Some(cut);
+                    }
+                }
+                run_start = None;
+            }
+            _ => {}
+        }
+    }
+
+    best
+}
+
+/// Splits a tall canvas into pages of roughly `page_height`, preferring to cut at gutters.
+pub fn split(img: &RgbImage, page_height: u32) -> Vec<RgbImage> {
+    let page_height = page_height.max(2);
+    let mut pages = Vec::new();
+    let mut top = 0;
+
+    // Don't leave a tiny page at the end, the last one can be a bit taller
+    while img.height() - top > page_height + page_height / 4 {
+        let target = top + page_height;
+        let to = (top + page_height * 3 / 2).min(img.height());
+        let cut = find_gutter(img, top + page_height / 2, to, target).unwrap_or(target);
+
+        pages.push(imageops::crop_imm(img, 0, top, img.width(), cut - top).to_image());
+        top = cut;
+    }
+
+    if top < img.height() {
+        pages.push(imageops::crop_imm(img, 0, top, img.width(), img.height() - top).to_image());
+    }
+
+    pages
+}
+
+/// Stitches all pages of a chapter and re-splits them according to `mode`.
+/// Returns new page names and bytes, split pages are JPEG and a tall image is PNG.
+pub fn stitch_chapter(
+    pages: Vec<(String, Vec<u8>)>,
+    mode: StitchMode,
+) -> anyhow::Result<Vec<(String, Vec<u8>)>> {
+    let strips = pages
+        .iter()
+        .map(|(_, bytes)| Ok(image::load_from_memory(bytes)?.to_rgb8()))
+        .collect::<anyhow::Result<Vec<RgbImage>>>()?;
+
+    if strips.is_empty() {
+        return Ok(pages);
+    }
+
+    let canvas = stitch(&strips);
+    drop(strips);
+
+    let mut output = Vec::new();
+
+    match mode {
+        StitchMode::Tall => {
+            // JPEG can't be taller than 65535 pixels
+            let mut buffer = Cursor::new(Vec::new());
+            canvas.write_to(&mut buffer, ImageFormat::Png)?;
+            output.push(("0000.png".to_string(), buffer.into_inner()));
+        }
+        StitchMode::Split { page_height } => {
+            let page_height = page_height.unwrap_or(canvas.width() * 8 / 5);
+
+            for (i, page) in split(&canvas, page_height).iter().enumerate() {
+                let mut buffer = Cursor::new(Vec::new());
+                page.write_with_encoder(JpegEncoder::new_with_quality(&mut buffer, 90))?;
+                output.push((format!("{:0>4}.jpg", i), buffer.into_inner()));
+            }
+        }
+    }
+
+    Ok(output)
+}
+
+/// Runs `stitch_chapter` on the rayon thread pool.
+pub async fn stitch_chapter_async(
+    pages: Vec<(String, Vec<u8>)>,
+    mode: StitchMode,
+) -> anyhow::Result<Vec<(String, Vec<u8>)>> {
+    run_on_pool(move || stitch_chapter(pages, mode)).await
+}
+
+#[cfg(test)]
+mod stitch_tests {
+    use super::*;
+
+    #[test]
+    fn test_stitch_scales_narrow_strips() {
+        let strips = vec![
+            RgbImage::from_pixel(100, 50, Rgb([0, 0, 0])),
+            RgbImage::from_pixel(50, 50, Rgb([0, 0, 0])),
+        ];
+        let canvas = stitch(&strips);
+
+        assert_eq!((canvas.width(), canvas.height()), (100, 150));
+    }
+
+    #[test]
+    fn test_split_prefers_gutters() {
+        // Textured panels with a white gutter between rows 700 and 720
+        let mut canvas =
+            RgbImage::from_fn(40, 1900, |x, y| Rgb([((x * 7 + y * 13) % 256) as u8, 0, 0]));
+        for y in 700..720 {
+            for x in 0..40 {
+                canvas.put_pixel(x, y, Rgb([255, 255, 255]));
+            }
+        }
+
+        let pages = split(&canvas, 1000);
+
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[0].height(), 710);
+        assert_eq!(pages[1].height(), 1190);
+    }
+
+    #[test]
+    fn test_split_solid_canvas() {
+        let canvas = RgbImage::from_pixel(40, 2100, Rgb([10, 20, 30]));
+        let pages = split(&canvas, 1000);
+
+        // A solid canvas is one big gutter, its middle is the target itself
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[0].height(), 1000);
+        assert_eq!(pages[1].height(), 1100);
+    }
+}
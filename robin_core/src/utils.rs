@@ -1,6 +1,5 @@
 use pbr::ProgressBar;
-
-pub const INT_FLOAT_REGEX: &str = r"[-+]?(?:\d*\.*\d+)";
+use sha2::{Digest, Sha256};
 
 pub fn capitalize(s: &str) -> String {
     let mut c = s.chars();
@@ -25,4 +24,9 @@ pub fn create_progress_bar(total: u64, msg: &str) -> ProgressBar<std::io::Stdout
     pb.show_message = true;
 
     pb
-}
\ No newline at end of file
+}
+
+/// SHA-256 of some bytes as hex.
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
@@ -0,0 +1,209 @@
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail};
+use image::{ImageError, ImageFormat};
+use reqwest::{Client, StatusCode};
+use sha2::{Digest, Sha256};
+use tokio::fs::{remove_file, rename, File};
+use tokio::io::AsyncWriteExt;
+
+use crate::filetype::{content_type, detect_extension};
+use crate::net::RateLimit;
+use crate::processing::run_on_pool;
+
+/// A page that was streamed to disk and passed validation.
+#[derive(Debug, Clone)]
+pub struct StoredPage {
+    /// File name inside the chapter folder
+    pub file_name: String,
+    /// Final URL of the response (after redirects)
+    pub url: String,
+    /// SHA-256 of the file as hex
+    pub sha256: String,
+    pub size: u64,
+}
+
+/// Checks the parts of a response that are known before the body is read.
+pub fn check_response(status: StatusCode, content_type: Option<&str>) -> anyhow::Result<()> {
+    if !status.is_success() {
+        bail!("HTTP status {}", status);
+    }
+
+    if let Some(ct) = content_type {
+        if ct.starts_with("text/") || ct.contains("html") || ct.contains("json") {
+            bail!("unexpected Content-Type: {}", ct);
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that the bytes are a complete image that decodes.
+pub fn check_image(bytes: &[u8]) -> anyhow::Result<()> {
+    let format = image::guess_format(bytes).map_err(|_| anyhow!("not an image"))?;
+
+    // Some encoders pad the file after the end marker, so only look near the end
+    if format == ImageFormat::Jpeg
+        && !bytes[bytes.len().saturating_sub(64)..]
+            .windows(2)
+            .any(|w| w == [0xFF, 0xD9])
+    {
+        bail!("truncated JPEG");
+    }
+
+    match image::load_from_memory_with_format(bytes, format) {
+        // e.g. AVIF without the `avif` feature, nothing to check then
+        Err(ImageError::Unsupported(_)) => Ok(()),
+        Err(e) => Err(anyhow!("image does not decode: {}", e)),
+        Ok(_) => Ok(()),
+    }
+}
+
+/// Checks that a response really is a complete image and not an error page or a cut off download.
+pub fn validate_page(
+    status: StatusCode,
+    content_type: Option<&str>,
+    content_length: Option<u64>,
+    bytes: &[u8],
+) -> anyhow::Result<()> {
+    check_response(status, content_type)?;
+
+    if let Some(len) = content_length {
+        if len != bytes.len() as u64 {
+            bail!("expected {} bytes, got {}", len, bytes.len());
+        }
+    }
+
+    check_image(bytes)
+}
+
+/// Streams a page into `dir/<stem>.part`, hashing it on the way, then validates it
+/// and renames it to `dir/<stem>.<ext>`.
+async fn try_fetch_page(
+    c: &Client,
+    url: &str,
+    dir: &Path,
+    stem: &str,
+) -> anyhow::Result<StoredPage> {
+    let mut response = c.get(url).send().await?;
+
+    let final_url = response.url().to_string();
+    let status = response.status();
+    let content_length = response.content_length();
+    let content_type = content_type(&response);
+
+    // Error pages are turned down before their body is read
+    check_response(status, content_type.as_deref())?;
+
+    let part_path = dir.join(format!("{}.part", stem));
+    let mut f = File::create(&part_path).await?;
+    let mut hasher = Sha256::new();
+    let mut size = 0u64;
+    let mut head: Vec<u8> = Vec::new();
+
+    while let Some(chunk) = response.chunk().await? {
+        if head.len() < 32 {
+            head.extend_from_slice(&chunk[..chunk.len().min(32 - head.len())]);
+        }
+        hasher.update(&chunk);
+        size += chunk.len() as u64;
+        f.write_all(&chunk).await?;
+    }
+    f.flush().await?;
+    drop(f);
+
+    // Decoding needs the whole page, but only one at a time and off the async threads
+    let check_path = part_path.clone();
+    let check_type = content_type.clone();
+    run_on_pool(move || {
+        let bytes = std::fs::read(check_path)?;
+        validate_page(status, check_type.as_deref(), content_length, &bytes)
+    })
+    .await?;
+
+    let file_name = format!(
+        "{}.{}",
+        stem,
+        detect_extension(content_type.as_deref(), &head)
+    );
+    rename(&part_path, dir.join(&file_name)).await?;
+
+    Ok(StoredPage {
+        file_name,
+        url: final_url,
+        sha256: format!("{:x}", hasher.finalize()),
+        size,
+    })
+}
+
+/// Downloads a page straight to `dir` and validates it, retrying up to `retries` times
+/// with a growing delay. Broken downloads are removed.
+pub async fn fetch_page_to_file(
+    c: &Client,
+    url: &str,
+    dir: &Path,
+    stem: String,
+    retries: usize,
+    rate_limit: Option<&RateLimit>,
+) -> anyhow::Result<StoredPage> {
+    let mut attempt = 0;
+
+    loop {
+        if let Some(limit) = rate_limit {
+            limit.wait().await;
+        }
+
+        match try_fetch_page(c, url, dir, &stem).await {
+            Ok(page) => return Ok(page),
+            Err(e) => {
+                let _ = remove_file(dir.join(format!("{}.part", stem))).await;
+
+                if attempt >= retries {
+                    return Err(e.context(format!(
+                        "{} failed after {} attempts",
+                        url,
+                        attempt + 1
+                    )));
+                }
+
+                attempt += 1;
+                tokio::time::sleep(Duration::from_millis(500 * attempt as u64)).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod validation_tests {
+    use super::*;
+    use image::{DynamicImage, RgbImage};
+    use std::io::Cursor;
+
+    fn sample_jpeg() -> Vec<u8> {
+        let mut buffer = Cursor::new(Vec::new());
+        DynamicImage::ImageRgb8(RgbImage::new(16, 16))
+            .write_to(&mut buffer, ImageFormat::Jpeg)
+            .unwrap();
+        buffer.into_inner()
+    }
+
+    #[test]
+    fn test_valid_page() {
+        let bytes = sample_jpeg();
+        let len = Some(bytes.len() as u64);
+        assert!(validate_page(StatusCode::OK, Some("image/jpeg"), len, &bytes).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_pages() {
+        let bytes = sample_jpeg();
+        let html = b"<html><body>Just a moment...</body></html>";
+
+        assert!(validate_page(StatusCode::FORBIDDEN, None, None, &bytes).is_err());
+        assert!(validate_page(StatusCode::OK, Some("text/html"), None, html).is_err());
+        assert!(validate_page(StatusCode::OK, None, None, html).is_err());
+        assert!(validate_page(StatusCode::OK, None, Some(10_000), &bytes).is_err());
+        assert!(validate_page(StatusCode::OK, None, None, &bytes[..bytes.len() / 2]).is_err());
+    }
+}